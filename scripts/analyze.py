@@ -13,7 +13,8 @@ def exec_lola(formula):
 def general_deadlock():
     # p_2 marks program termination
     #exec_lola('--formula=AG(EF(p_2 = 1))')
-    exec_lola('--formula=EF (DEADLOCK AND (p_2 = 0 AND p_0 = 0))')
+    # p_3 marks a cut off recursion
+    exec_lola('--formula=EF (DEADLOCK AND (p_2 = 0 AND p_0 = 0 AND p_3 = 0))')
 
 def unconditional_deadlock():
     exec_lola('--formula=EF DEADLOCK')
@@ -22,6 +23,10 @@ def can_panic():
     # p_0 marks panic or unwind. Implies p_2
     exec_lola('--formula=EF p_0 > 0')
 
+def recursion_bound():
+    # p_3 marks recursions that were deeper than the unrolling depth
+    exec_lola('--formula=EF p_3 > 0')
+
 def neighbors(nodes):
     #nodes_regex = [node + "\W" for node in nodes]
     neighbors = []
@@ -63,6 +68,7 @@ if __name__ == "__main__":
     parser.add_argument('-d', '--deadlock',action="store_true", help="Default search for deadlocks. Successful and unsuccessful termination is not concidered as a deadlock")
     parser.add_argument('-u', '--unconditional-deadlock',action="store_true", help="Search for every deadlock. Even program termination is concidered a deadlock")
     parser.add_argument('-p', '--panic',action="store_true", help="Check if it is possible to reach a panic state")
+    parser.add_argument('-r', '--recursion',action="store_true", help="Check if a recursion can exceed the unrolling depth used in the translation")
     parser.add_argument('-n', '--neighbors', nargs="*", help="Generates a subnet with the given nodes and all its neighbors and visualizes it.")
     parser.add_argument('-v', '--visualize', action="store_true", help="visualize the graph in graphviz. Doesn't terminate (in time) for larger graphs")
 
@@ -74,6 +80,8 @@ if __name__ == "__main__":
         unconditional_deadlock()
     if args.panic:
         can_panic()
+    if args.recursion:
+        recursion_bound()
     if args.neighbors:
         neighbors(args.neighbors)
     if args.visualize:
@@ -0,0 +1,266 @@
+// inspired by and based on cargo-miri: https://github.com/rust-lang/miri/blob/master/src/bin/cargo-miri.rs
+
+//! `cargo granite` translates the crates of a cargo workspace into petri nets.
+//!
+//! Cargo runs this binary in two roles. As the `granite` subcommand it starts `cargo check`
+//! with itself as `RUSTC_WORKSPACE_WRAPPER`. As that wrapper it is called for every rustc
+//! invocation of a workspace crate: the crate is compiled as usual (later crates need its
+//! metadata) and selected crates are additionally handed to the granite driver.
+//! Dependencies outside of the workspace never reach the wrapper.
+
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+const CARGO_GRANITE_HELP: &str = r#"Translates the crates of a cargo workspace into petri nets
+
+Usage:
+    cargo granite [<cargo options>...] [-- <granite options>...]
+
+Cargo options select packages, targets and features like for `cargo check`, e.g.:
+    -p <SPEC>, --lib, --bin <NAME>, --bins, --tests, --all-targets, --features <FEATURES>
+
+Granite options are passed to every translation, e.g.:
+    --format <FORMAT>..., --entry <PATH>, --entries <MODE>, --lock-api <FILE>
+Without entry options libraries are analyzed from their public functions
+and test targets from their test functions.
+
+The crates are checked in `target/granite`, so regular build artifacts are kept.
+The nets of every target are written to `target/granite/<crate>-<kind>/`.
+"#;
+
+/// The granite options of the subcommand, separated by newlines.
+const GRANITE_ARGS: &str = "GRANITE_ARGS";
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    target_directory: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+}
+
+fn show_help() {
+    println!("{}", CARGO_GRANITE_HELP);
+}
+
+fn show_error(msg: String) -> ! {
+    eprintln!("fatal error: {}", msg);
+    process::exit(1)
+}
+
+fn exit_with(status: process::ExitStatus) -> ! {
+    process::exit(status.code().unwrap_or(1))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        // `cargo granite ...` calls `cargo-granite granite ...`
+        Some("granite") => cargo_granite(&args[2..]),
+        // `cargo` calls the wrapper with the path of rustc followed by its arguments
+        Some(_) if env::var_os(GRANITE_ARGS).is_some() => rustc_wrapper(&args[1..]),
+        _ => {
+            show_help();
+            process::exit(1)
+        }
+    }
+}
+
+fn cargo_granite(args: &[String]) {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        show_help();
+        return;
+    }
+    let (cargo_args, granite_args) = match args.iter().position(|arg| arg == "--") {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (args, &[][..]),
+    };
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let metadata = metadata(&cargo);
+    // a separate target directory keeps the artifacts of regular builds untouched
+    let target_dir = metadata.target_directory.join("granite");
+
+    // checked crates are fresh for cargo, so they would not reach the wrapper again
+    let mut clean = Command::new(&cargo);
+    clean.arg("clean").arg("--target-dir").arg(&target_dir);
+    for package in selected_packages(&metadata, cargo_args) {
+        clean.arg("--package").arg(package);
+    }
+    let status = clean
+        .status()
+        .unwrap_or_else(|err| show_error(format!("unable to run cargo clean: {}", err)));
+    if !status.success() {
+        exit_with(status)
+    }
+
+    let wrapper = env::current_exe()
+        .unwrap_or_else(|err| show_error(format!("unable to locate cargo-granite: {}", err)));
+    let mut check = Command::new(&cargo);
+    check
+        .arg("check")
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .args(cargo_args)
+        .env("RUSTC_WORKSPACE_WRAPPER", wrapper)
+        .env(GRANITE_ARGS, granite_args.join("\n"))
+        // the driver is linked against the rustc libraries of the toolchain
+        .env(library_path_var(), library_path());
+    let status = check
+        .status()
+        .unwrap_or_else(|err| show_error(format!("unable to run cargo check: {}", err)));
+    exit_with(status)
+}
+
+fn metadata(cargo: &str) -> Metadata {
+    let output = Command::new(cargo)
+        .args(&["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .unwrap_or_else(|err| show_error(format!("unable to run cargo metadata: {}", err)));
+    if !output.status.success() {
+        exit_with(output.status)
+    }
+    serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|err| show_error(format!("unable to read cargo metadata: {}", err)))
+}
+
+/// The packages given with `-p` or all workspace members.
+fn selected_packages(metadata: &Metadata, cargo_args: &[String]) -> Vec<String> {
+    let mut packages = Vec::new();
+    let mut args = cargo_args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-p" || arg == "--package" {
+            packages.extend(args.next().cloned());
+        } else if arg.starts_with("--package=") {
+            packages.push(arg["--package=".len()..].to_string());
+        }
+    }
+    if !packages.is_empty() {
+        return packages;
+    }
+    metadata
+        .packages
+        .iter()
+        .map(|package| package.name.clone())
+        .collect()
+}
+
+fn library_path_var() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else if cfg!(windows) {
+        "PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    }
+}
+
+/// The lib folder of the toolchain sysroot in front of the current library path.
+fn library_path() -> String {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let output = Command::new(rustc)
+        .args(&["--print", "sysroot"])
+        .output()
+        .unwrap_or_else(|err| show_error(format!("unable to determine the sysroot: {}", err)));
+    let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let lib = Path::new(&sysroot).join(if cfg!(windows) { "bin" } else { "lib" });
+    let separator = if cfg!(windows) { ";" } else { ":" };
+    match env::var(library_path_var()) {
+        Ok(path) if !path.is_empty() => format!("{}{}{}", lib.display(), separator, path),
+        _ => lib.display().to_string(),
+    }
+}
+
+/// The value of a rustc option, e.g. `--crate-name foo`.
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let prefix = format!("{}=", name);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next().map(String::as_str);
+        }
+        if arg.starts_with(&prefix) {
+            return Some(&arg[prefix.len()..]);
+        }
+    }
+    None
+}
+
+fn rustc_wrapper(args: &[String]) {
+    let (rustc, rustc_args) = args.split_first().expect("no rustc given");
+    let status = Command::new(rustc)
+        .args(rustc_args)
+        .status()
+        .unwrap_or_else(|err| show_error(format!("unable to run rustc: {}", err)));
+    if !status.success() {
+        exit_with(status)
+    }
+    // version queries and crates of unselected packages only need rustc
+    let crate_name = match arg_value(rustc_args, "--crate-name") {
+        Some(crate_name) => crate_name,
+        None => return,
+    };
+    let crate_type = arg_value(rustc_args, "--crate-type").unwrap_or("bin");
+    if env::var_os("CARGO_PRIMARY_PACKAGE").is_none()
+        || crate_name == "build_script_build"
+        || crate_type == "proc-macro"
+    {
+        return;
+    }
+    let is_test = rustc_args.iter().any(|arg| arg == "--test");
+
+    let mut granite_args: Vec<String> = env::var(GRANITE_ARGS)
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect();
+    if !granite_args
+        .iter()
+        .any(|arg| arg.starts_with("--entry") || arg.starts_with("--entries"))
+    {
+        if is_test {
+            granite_args.extend(vec!["--entries".into(), "tests".into()]);
+        } else if crate_type != "bin" {
+            granite_args.extend(vec!["--entries".into(), "pub".into()]);
+        }
+    }
+
+    // target/granite/debug/deps -> target/granite/<crate>-<kind>
+    let kind = if is_test { "test" } else { crate_type };
+    let out_dir = arg_value(rustc_args, "--out-dir")
+        .and_then(|deps| Path::new(deps).parent().and_then(Path::parent))
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("granite"))
+        .join(format!("{}-{}", crate_name, kind));
+    std::fs::create_dir_all(&out_dir).unwrap_or_else(|err| {
+        show_error(format!("unable to create {}: {}", out_dir.display(), err))
+    });
+
+    // the driver runs in the output directory, so the sources need absolute paths
+    let current_dir = env::current_dir()
+        .unwrap_or_else(|err| show_error(format!("unable to read current directory: {}", err)));
+    let driver_args = rustc_args.iter().map(|arg| {
+        if arg.ends_with(".rs") && Path::new(arg).is_relative() {
+            current_dir.join(arg).display().to_string()
+        } else {
+            arg.clone()
+        }
+    });
+    let driver = env::current_exe()
+        .unwrap_or_else(|err| show_error(format!("unable to locate cargo-granite: {}", err)))
+        .with_file_name(format!("granite{}", env::consts::EXE_SUFFIX));
+    let status = Command::new(&driver)
+        .args(driver_args)
+        .arg("--")
+        .args(granite_args)
+        .current_dir(&out_dir)
+        .status()
+        .unwrap_or_else(|err| show_error(format!("unable to run {}: {}", driver.display(), err)));
+    if !status.success() {
+        exit_with(status)
+    }
+}
@@ -0,0 +1,111 @@
+//! Selection of the functions that are translated as roots of a net.
+//!
+//! Binary crates start at `main`. Library crates have no such function, so any
+//! function can be given by its path or all public (or test) functions are used.
+
+use rustc::ty::TyCtxt;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EntryMode {
+    /// the `main` function of a binary crate
+    Main,
+    /// every function without generic parameters that is reachable from outside the crate
+    Public,
+    /// every `#[test]` function, the crate has to be compiled with `--test`
+    Tests,
+}
+
+impl EntryMode {
+    pub fn from_arg(mode: &str) -> Result<Self, String> {
+        match mode {
+            "main" => Ok(EntryMode::Main),
+            "pub" => Ok(EntryMode::Public),
+            "tests" => Ok(EntryMode::Tests),
+            _ => Err(format!("unknown entry mode {}", mode)),
+        }
+    }
+}
+
+/// Finds the roots of the analysis. An explicit path takes precedence over the mode.
+pub fn entry_points(tcx: TyCtxt<'_>, path: Option<&str>, mode: EntryMode) -> Vec<DefId> {
+    let functions = tcx.body_owners().filter(|def_id| is_function(tcx, *def_id));
+    if let Some(path) = path {
+        return functions
+            .filter(|def_id| matches_path(tcx, *def_id, path))
+            .collect();
+    }
+    match mode {
+        EntryMode::Main => match tcx.entry_fn(LOCAL_CRATE) {
+            Some((def_id, _)) => vec![def_id],
+            None => Vec::new(),
+        },
+        EntryMode::Public => functions
+            // a `pub` function in a private module is not exported
+            .filter(|def_id| {
+                tcx.hir().as_local_hir_id(*def_id).map_or(false, |hir_id| {
+                    tcx.privacy_access_levels(LOCAL_CRATE).is_exported(hir_id)
+                })
+            })
+            .filter(|def_id| {
+                // generic functions cannot be translated without concrete types
+                let is_generic = has_type_params(tcx, *def_id);
+                if is_generic {
+                    info!("skipping generic entry {}", tcx.def_path_str(*def_id));
+                }
+                !is_generic
+            })
+            .collect(),
+        EntryMode::Tests => {
+            // the test harness adds a const with the name of the test next to it
+            let tests: Vec<_> = tcx
+                .body_owners()
+                .filter(|def_id| {
+                    tcx.get_attrs(*def_id)
+                        .iter()
+                        .any(|attr| &*attr.name_or_empty().as_str() == "rustc_test_marker")
+                })
+                .map(|def_id| (tcx.parent(def_id), tcx.item_name(def_id)))
+                .collect();
+            functions
+                .filter(|def_id| tests.contains(&(tcx.parent(*def_id), tcx.item_name(*def_id))))
+                .collect()
+        }
+    }
+}
+
+/// A name of the entry that can be part of a file name.
+pub fn entry_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    tcx.def_path_str(def_id)
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Whether the item or its parent (e.g. an impl) has type or const parameters.
+/// Lifetime parameters are erased and need no concrete arguments.
+pub fn has_type_params(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    let generics = tcx.generics_of(def_id);
+    let counts = generics.own_counts();
+    counts.types + counts.consts > 0
+        || generics
+            .parent
+            .map_or(false, |parent| has_type_params(tcx, parent))
+}
+
+fn is_function(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    match tcx.def_kind(def_id) {
+        Some(DefKind::Fn) | Some(DefKind::Method) => true,
+        _ => false,
+    }
+}
+
+/// Paths may be given with or without the crate name.
+fn matches_path(tcx: TyCtxt<'_>, def_id: DefId, path: &str) -> bool {
+    let crate_prefix = format!("{}::", tcx.crate_name(LOCAL_CRATE));
+    let path = path
+        .trim_start_matches("crate::")
+        .trim_start_matches(crate_prefix.as_str());
+    tcx.def_path_str(def_id) == path
+}
@@ -0,0 +1,218 @@
+//! `granite explain` turns a witness path of LoLa into a trace of the rust program.
+//!
+//! ```text
+//! lola net.lola --formula="EF DEADLOCK" --path=path.txt --state=state.txt
+//! granite explain --spans net.spans.json --path path.txt --state state.txt
+//! ```
+//!
+//! Every transition of the path is resolved with the map of `--format spans`.
+//! Consecutive steps of the same thread are grouped and the trace ends with the
+//! operations every thread is blocked at. These are the marked control flow places
+//! of the witness state, or of the marking that results from replaying the path
+//! on the net if only the net is given.
+
+use crate::lola::LolaNet;
+use crate::spans::{Locations, SourceLocation, SpanKind};
+use clap::{App, Arg, ArgMatches};
+use std::collections::HashMap;
+use std::io::Read;
+
+const MAIN_THREAD: &str = "main";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let arguments = App::new("granite explain")
+        .about("Maps a witness path of LoLa back to the rust source")
+        .arg(
+            Arg::with_name("spans")
+                .long("spans")
+                .value_name("FILE")
+                .help("The node locations generated with `--format spans`")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("path")
+                .long("path")
+                .value_name("FILE")
+                .help("The witness path printed by LoLa (`-` for stdin)")
+                .default_value("-"),
+        )
+        .arg(
+            Arg::with_name("state")
+                .long("state")
+                .value_name("FILE")
+                .help("The witness state printed by LoLa")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("net")
+                .long("net")
+                .value_name("FILE")
+                .help("The net in LoLa format, replays the path if no witness state is given")
+                .required(false),
+        )
+        .get_matches_from(args);
+
+    let locations: Locations = serde_json::from_str(&read(value(&arguments, "spans"))?)
+        .map_err(|err| format!("unable to parse {}: {}", value(&arguments, "spans"), err))?;
+    let path = witness_path(&read(value(&arguments, "path"))?);
+    if path.is_empty() {
+        return Err("the witness path contains no transitions".into());
+    }
+    let marking = match (arguments.value_of("state"), arguments.value_of("net")) {
+        (Some(state), _) => Some(witness_state(&read(state)?)),
+        (None, Some(net)) => Some(LolaNet::parse(&read(net)?).replay(&path)?),
+        (None, None) => None,
+    };
+
+    print_trace(&path, &locations);
+    match marking {
+        Some(marking) => print_blocked(&marking, &locations),
+        None => print_last_steps(&path, &locations),
+    }
+    Ok(())
+}
+
+fn value<'a>(arguments: &'a ArgMatches<'_>, name: &str) -> &'a str {
+    arguments.value_of(name).expect("missing argument")
+}
+
+fn read(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|err| format!("unable to read stdin: {}", err))?;
+        return Ok(content);
+    }
+    std::fs::read_to_string(path).map_err(|err| format!("unable to read {}: {}", path, err))
+}
+
+/// The transition ids in the output of `lola --path`, other output is skipped.
+fn witness_path(output: &str) -> Vec<String> {
+    output
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| is_node_id(token, 't'))
+        .map(String::from)
+        .collect()
+}
+
+/// The marked places in the output of `lola --state`, one `p_3 : 1` per line.
+fn witness_state(output: &str) -> HashMap<String, u32> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split(':');
+            let place = parts.next()?.trim();
+            if !is_node_id(place, 'p') {
+                return None;
+            }
+            let tokens = parts
+                .next()
+                .and_then(|tokens| tokens.trim().parse().ok())
+                .unwrap_or(1);
+            Some((place.to_string(), tokens))
+        })
+        .collect()
+}
+
+fn is_node_id(token: &str, kind: char) -> bool {
+    let mut chars = token.chars();
+    chars.next() == Some(kind)
+        && chars.next() == Some('_')
+        && !chars.as_str().is_empty()
+        && chars.all(|c| c.is_ascii_digit())
+}
+
+fn thread_name(location: Option<&SourceLocation>) -> &str {
+    location
+        .and_then(|location| location.thread.as_ref())
+        .map(String::as_str)
+        .unwrap_or(MAIN_THREAD)
+}
+
+fn print_trace(path: &[String], locations: &Locations) {
+    let threads: Vec<&str> = {
+        let mut threads: Vec<&str> = path
+            .iter()
+            .map(|step| thread_name(locations.get(step)))
+            .collect();
+        threads.sort();
+        threads.dedup();
+        threads
+    };
+    println!(
+        "witness path with {} steps in {} thread(s): {}",
+        path.len(),
+        threads.len(),
+        threads.join(", ")
+    );
+    let mut current_thread = None;
+    let mut previous: Option<&SourceLocation> = None;
+    for (index, step) in path.iter().enumerate() {
+        let location = locations.get(step);
+        let thread = thread_name(location);
+        if current_thread != Some(thread) {
+            println!("\n[{}]", thread);
+            current_thread = Some(thread);
+            previous = None;
+        }
+        match location {
+            // the alternatives and bookkeeping of one statement are shown once
+            Some(location) if is_same(previous, location) => {}
+            Some(location) => println!("{:>5} {:>7}  {}", index + 1, step, location),
+            None => println!("{:>5} {:>7}  (no source location)", index + 1, step),
+        }
+        previous = location;
+    }
+}
+
+fn is_same(previous: Option<&SourceLocation>, location: &SourceLocation) -> bool {
+    match previous {
+        Some(previous) => {
+            previous.function == location.function
+                && previous.block == location.block
+                && previous.statement == location.statement
+                && previous.span == location.span
+        }
+        None => false,
+    }
+}
+
+/// Control flow that stops at the end of a block waits for its terminator,
+/// e.g. a lock that cannot be acquired.
+fn print_blocked(marking: &HashMap<String, u32>, locations: &Locations) {
+    let mut blocked: Vec<(&str, &String, &SourceLocation)> = marking
+        .iter()
+        .filter(|(_, tokens)| **tokens > 0)
+        .filter_map(|(place, _)| Some((place, locations.get(place)?)))
+        .filter(|(_, location)| location.kind == SpanKind::Terminator)
+        .map(|(place, location)| (thread_name(Some(location)), place, location))
+        .collect();
+    blocked.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    if blocked.is_empty() {
+        println!("\nno thread is blocked at a terminator in the final state");
+        return;
+    }
+    println!("\nblocked operations:");
+    for (thread, place, location) in blocked {
+        println!("[{}] {:>7}  {}", thread, place, location);
+        for caller in &location.callers {
+            println!("{:>18}called at {}", "", caller);
+        }
+    }
+}
+
+fn print_last_steps(path: &[String], locations: &Locations) {
+    let mut last_steps: Vec<(&str, &String, &SourceLocation)> = Vec::new();
+    for step in path {
+        if let Some(location) = locations.get(step) {
+            let thread = thread_name(Some(location));
+            last_steps.retain(|(other, _, _)| *other != thread);
+            last_steps.push((thread, step, location));
+        }
+    }
+    println!("\nlast steps (pass `--state` or `--net` for the blocked operations):");
+    for (thread, step, location) in last_steps {
+        println!("[{}] {:>7}  {}", thread, step, location);
+    }
+}
@@ -0,0 +1,108 @@
+//! Mapping of lock implementations onto the mutex net pattern.
+//!
+//! Besides `std::sync::Mutex` users can declare their own lock types
+//! (e.g. of `parking_lot`, `spin` or hand written wrappers) in a toml file:
+//!
+//! ```toml
+//! [[lock]]
+//! # type paths as printed by rustc (without generic parameters)
+//! types = ["lock_api::mutex::Mutex"]
+//! # function paths as printed by rustc, a name matches a path or its end,
+//! # e.g. `Mutex::<R, T>::lock` matches the lock function above but not `try_lock`
+//! constructors = ["lock_api::mutex::Mutex::<R, T>::new"]
+//! acquire = ["lock_api::mutex::Mutex::<R, T>::lock"]
+//! try_acquire = ["lock_api::mutex::Mutex::<R, T>::try_lock"]
+//! release = ["lock_api::mutex::Mutex::<R, T>::force_unlock"]
+//! ```
+//!
+//! Acquire functions return a guard that releases the lock when dropped.
+//! Release functions take either such a guard or the lock itself as first argument.
+
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LockOperation {
+    Constructor,
+    Acquire,
+    TryAcquire,
+    Release,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LockMapping {
+    pub types: Vec<String>,
+    pub constructors: Vec<String>,
+    pub acquire: Vec<String>,
+    pub try_acquire: Vec<String>,
+    pub release: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LockApi {
+    #[serde(default)]
+    lock: Vec<LockMapping>,
+}
+
+impl Default for LockApi {
+    /// Only knows `std::sync::Mutex`.
+    /// It has no release function, the mutex is released when its guard is dropped.
+    fn default() -> Self {
+        LockApi {
+            lock: vec![LockMapping {
+                types: vec!["std::sync::Mutex".into()],
+                constructors: vec!["std::sync::Mutex::<T>::new".into()],
+                acquire: vec!["std::sync::Mutex::<T>::lock".into()],
+                try_acquire: vec!["std::sync::Mutex::<T>::try_lock".into()],
+                release: Vec::new(),
+            }],
+        }
+    }
+}
+
+impl LockApi {
+    /// Reads additional mappings from a toml file.
+    /// The mapping of `std::sync::Mutex` is always included.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let user_api: LockApi = toml::from_str(&content)?;
+        let mut api = LockApi::default();
+        api.lock.extend(user_api.lock);
+        Ok(api)
+    }
+
+    /// Checks the result of `sort_string` of a type (e.g. "struct `std::sync::Mutex`").
+    pub fn is_lock_type(&self, sort_string: &str) -> bool {
+        self.lock.iter().any(|mapping| {
+            mapping
+                .types
+                .iter()
+                .any(|path| sort_string == format!("struct `{}`", path))
+        })
+    }
+
+    pub fn operation(&self, fn_name: &str) -> Option<LockOperation> {
+        // the path has to end at the last segment, `lock` is no prefix of `lock_timeout`
+        let matches = |paths: &[String]| {
+            paths
+                .iter()
+                .any(|path| fn_name == path.as_str() || fn_name.ends_with(&format!("::{}", path)))
+        };
+        for mapping in &self.lock {
+            if matches(&mapping.try_acquire) {
+                return Some(LockOperation::TryAcquire);
+            }
+            if matches(&mapping.constructors) {
+                return Some(LockOperation::Constructor);
+            }
+            if matches(&mapping.acquire) {
+                return Some(LockOperation::Acquire);
+            }
+            if matches(&mapping.release) {
+                return Some(LockOperation::Release);
+            }
+        }
+        None
+    }
+}
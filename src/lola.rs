@@ -0,0 +1,117 @@
+//! A reader for nets in LoLa syntax as written by granite.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct LolaNet {
+    pub marking: HashMap<String, u32>,
+    pub transitions: Vec<LolaTransition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LolaTransition {
+    pub name: String,
+    pub consume: Vec<(String, u32)>,
+    pub produce: Vec<(String, u32)>,
+}
+
+impl LolaNet {
+    pub fn parse(lola: &str) -> Self {
+        let lola = strip_comments(lola);
+        let marking = match lola.split("MARKING").nth(1) {
+            Some(marking) => arcs(marking.split(';').next().unwrap_or_default())
+                .into_iter()
+                .collect(),
+            None => HashMap::new(),
+        };
+        let transitions = lola
+            .split("TRANSITION")
+            .skip(1)
+            .filter_map(|transition| {
+                let name = transition.split_whitespace().next()?;
+                let consume = section(transition, "CONSUME");
+                let produce = section(transition, "PRODUCE");
+                Some(LolaTransition {
+                    name: name.trim_end_matches(';').to_string(),
+                    consume: arcs(consume),
+                    produce: arcs(produce),
+                })
+            })
+            .collect();
+        LolaNet {
+            marking,
+            transitions,
+        }
+    }
+
+    pub fn transition(&self, name: &str) -> Option<&LolaTransition> {
+        self.transitions
+            .iter()
+            .find(|transition| transition.name == name)
+    }
+
+    /// Fires the transitions in order, starting from the initial marking.
+    pub fn replay(&self, path: &[String]) -> Result<HashMap<String, u32>, String> {
+        let mut marking = self.marking.clone();
+        for step in path {
+            let transition = self
+                .transition(step)
+                .ok_or_else(|| format!("unknown transition {}", step))?;
+            for (place, weight) in &transition.consume {
+                let tokens = marking.entry(place.clone()).or_insert(0);
+                if *tokens < *weight {
+                    return Err(format!("{} is not enabled at {}", step, place));
+                }
+                *tokens -= weight;
+            }
+            for (place, weight) in &transition.produce {
+                *marking.entry(place.clone()).or_insert(0) += weight;
+            }
+        }
+        Ok(marking)
+    }
+}
+
+/// Comments are enclosed in braces and node names may contain braces themselves.
+fn strip_comments(lola: &str) -> String {
+    let mut depth = 0;
+    lola.chars()
+        .filter(|c| {
+            match c {
+                '{' => depth += 1,
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    return false;
+                }
+                _ => {}
+            }
+            depth == 0
+        })
+        .collect()
+}
+
+/// The text between a keyword and the next semicolon.
+fn section<'a>(transition: &'a str, keyword: &str) -> &'a str {
+    match transition.split(keyword).nth(1) {
+        Some(section) => section.split(';').next().unwrap_or_default(),
+        None => "",
+    }
+}
+
+/// A list like `p_1: 1, p_3: 2`.
+fn arcs(list: &str) -> Vec<(String, u32)> {
+    list.split(',')
+        .filter_map(|arc| {
+            let mut parts = arc.split(':');
+            let place = parts.next()?.trim();
+            if place.is_empty() {
+                return None;
+            }
+            let weight = parts
+                .next()
+                .and_then(|weight| weight.trim().parse().ok())
+                .unwrap_or(1);
+            Some((place.to_string(), weight))
+        })
+        .collect()
+}
@@ -14,16 +14,32 @@ extern crate rustc_index;
 extern crate rustc_interface;
 extern crate rustc_mir;
 
+mod entry;
+mod explain;
 mod init;
+mod lock_api;
+mod lola;
 mod petri_net;
+mod spans;
 mod translator;
 
-use crate::translator::Translator;
+use crate::entry::EntryMode;
+use crate::lock_api::LockApi;
+use crate::petri_net::net::Net;
+use crate::spans::SpanTable;
+use crate::translator::{Translator, TranslatorConfig};
 use clap::{Arg, ArgMatches};
+use rustc::ty::TyCtxt;
 use rustc_driver::Compilation;
-use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
 use rustc_interface::interface;
 use rustc_interface::Queries;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const FORMATS: &[&str] = &["pnml", "lola", "dot", "spans"];
 
 struct PetriConfig<'a> {
     arguments: ArgMatches<'a>,
@@ -39,31 +55,8 @@ impl<'a> rustc_driver::Callbacks for PetriConfig<'a> {
         compiler.session().abort_if_errors();
 
         queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
-            let (entry_def_id, _) = tcx.entry_fn(LOCAL_CRATE).expect("no main function found!");
-            let mir_dump = match self.arguments.values_of("mir_dump") {
-                Some(path) => Some(out_file("mir")),
-                None => None,
-            };
-            let mut pass = Translator::new(tcx, mir_dump).expect("Unable to create translator");
-            let net = pass.petrify(entry_def_id).expect("translation failed");
-            for format in self
-                .arguments
-                .values_of("output_format")
-                .expect("no output format given")
-            {
-                let mut file = out_file(format);
-                if format == "pnml" {
-                    info!("generating pnml");
-                    net.to_pnml(&mut file).expect("write error");
-                }
-                if format == "lola" {
-                    info!("generating lola");
-                    net.to_lola(&mut file).expect("write error");
-                }
-                if format == "dot" {
-                    info!("generating dot");
-                    net.to_dot(&mut file).expect("write error");
-                }
+            if let Err(msg) = self.translate(tcx) {
+                tcx.sess.err(&msg);
             }
         });
 
@@ -72,8 +65,216 @@ impl<'a> rustc_driver::Callbacks for PetriConfig<'a> {
         Compilation::Stop
     }
 }
+
+impl<'a> PetriConfig<'a> {
+    fn translate(&self, tcx: TyCtxt<'_>) -> Result<(), String> {
+        let entries = entry::entry_points(
+            tcx,
+            self.arguments.value_of("entry"),
+            EntryMode::from_arg(self.arguments.value_of("entries").expect("no mode given"))?,
+        );
+        if entries.is_empty() {
+            return Err(
+                "no entry point found, use `--entry` or `--entries` for library crates".into(),
+            );
+        }
+        let out_dir = Path::new(
+            self.arguments
+                .value_of("out_dir")
+                .expect("no output directory"),
+        );
+        fs::create_dir_all(out_dir)
+            .map_err(|err| format!("unable to create {}: {}", out_dir.display(), err))?;
+        let outputs = self.explicit_outputs()?;
+        // the outputs would be interleaved on stdout
+        let mut stdout_writers: Vec<&str> = outputs
+            .iter()
+            .filter(|(_, path)| **path == "-")
+            .map(|(format, _)| *format)
+            .collect();
+        if self.arguments.value_of("mir_dump") == Some("-") {
+            stdout_writers.push("mir dump");
+        }
+        if stdout_writers.len() > 1 {
+            stdout_writers.sort();
+            return Err(format!(
+                "only one output can be written to stdout, found {}",
+                stdout_writers.join(", ")
+            ));
+        }
+        let lock_api = match self.arguments.value_of("lock_api") {
+            Some(path) => LockApi::from_file(path)
+                .map_err(|err| format!("unable to read lock api mapping {}: {}", path, err))?,
+            None => LockApi::default(),
+        };
+        let config = TranslatorConfig {
+            spurious_wakeups: self.arguments.is_present("spurious_wakeups"),
+            recursion_depth: self.count("recursion_depth"),
+            function_summaries: self.arguments.is_present("function_summaries"),
+            lock_api,
+            field_depth: self.count("field_depth"),
+            ownership: self.arguments.is_present("ownership"),
+            loop_instances: self.count("loop_instances"),
+        };
+
+        let prefix = self.arguments.value_of("output_prefix");
+        let nets: Vec<(String, Vec<DefId>)> =
+            if entries.len() == 1 || self.arguments.is_present("combined") {
+                vec![(prefix.unwrap_or("net").to_string(), entries)]
+            } else {
+                // every entry gets its own net, named after the crate unless a prefix is given
+                if let Some((format, path)) = outputs.iter().next() {
+                    return Err(format!(
+                        "{} nets cannot be written to the single {} output {}, use `--combined`",
+                        entries.len(),
+                        format,
+                        path
+                    ));
+                }
+                let crate_name = tcx.crate_name(LOCAL_CRATE).to_string();
+                let prefix = prefix.unwrap_or(&crate_name);
+                entries
+                    .into_iter()
+                    .map(|entry| {
+                        let stem = format!("{}_{}", prefix, entry::entry_name(tcx, entry));
+                        (stem, vec![entry])
+                    })
+                    .collect()
+            };
+
+        // an explicit mir dump file collects the functions of all nets
+        let mir_dump_file = match self.arguments.value_of("mir_dump") {
+            Some(path) if path != "-" => Some(
+                File::create(path).map_err(|err| format!("unable to create {}: {}", path, err))?,
+            ),
+            _ => None,
+        };
+        for (stem, entries) in nets {
+            let mir_dump: Option<Box<dyn Write>> = if !self.arguments.is_present("mir_dump") {
+                None
+            } else if let Some(file) = &mir_dump_file {
+                let file = file
+                    .try_clone()
+                    .map_err(|err| format!("unable to share the mir dump: {}", err))?;
+                Some(Box::new(file))
+            } else {
+                let path = self
+                    .arguments
+                    .value_of("mir_dump")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| out_dir.join(format!("{}.mir", stem)));
+                Some(create_output(&path)?)
+            };
+            let mut pass = Translator::new(tcx, mir_dump, config.clone())
+                .map_err(|err| format!("unable to create translator: {:?}", err))?;
+            pass.petrify(&entries)
+                .map_err(|err| format!("translation failed: {:?}", err))?;
+            if let Some(err) = pass.take_mir_dump_error() {
+                return Err(format!("unable to dump mir: {}", err));
+            }
+            self.write_net(pass.net(), pass.spans(), out_dir, &stem, &outputs)?;
+        }
+        Ok(())
+    }
+
+    /// The value of a numeric argument, which is checked by `is_count`.
+    fn count(&self, name: &str) -> usize {
+        self.arguments
+            .value_of(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| panic!("no valid {} given", name))
+    }
+
+    /// The paths given with `--output FORMAT=PATH`.
+    fn explicit_outputs(&self) -> Result<HashMap<&str, &str>, String> {
+        let mut outputs = HashMap::new();
+        for output in self.arguments.values_of("output").into_iter().flatten() {
+            let mut parts = output.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(format), Some(path)) if FORMATS.contains(&format) && !path.is_empty() => {
+                    if outputs.insert(format, path).is_some() {
+                        return Err(format!("more than one {} output given", format));
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid output {}, expected FORMAT=PATH with one of {}",
+                        output,
+                        FORMATS.join(", ")
+                    ))
+                }
+            }
+        }
+        Ok(outputs)
+    }
+
+    fn write_net(
+        &self,
+        net: &Net,
+        spans: &SpanTable,
+        out_dir: &Path,
+        stem: &str,
+        outputs: &HashMap<&str, &str>,
+    ) -> Result<(), String> {
+        // formats with an explicit output are written even if they are missing in `--format`
+        let mut formats: Vec<&str> = self
+            .arguments
+            .values_of("output_format")
+            .expect("no output format given")
+            .collect();
+        for format in outputs.keys() {
+            if !formats.contains(format) {
+                formats.push(format);
+            }
+        }
+        let locations = spans.resolve(net);
+        for format in formats {
+            let path = match outputs.get(format) {
+                Some(path) => PathBuf::from(path),
+                None => out_dir.join(format!("{}.{}", stem, extension(format))),
+            };
+            info!("generating {}", format);
+            let content = match format {
+                "lola" => {
+                    let mut lola = Vec::new();
+                    net.to_lola(&mut lola)
+                        .map_err(|err| format!("unable to generate lola: {:?}", err))?;
+                    lola
+                }
+                "pnml" => {
+                    let mut pnml = Vec::new();
+                    net.to_pnml(&mut pnml)
+                        .map_err(|err| format!("unable to generate pnml: {:?}", err))?;
+                    spans::annotate_pnml(&String::from_utf8_lossy(&pnml), &locations).into_bytes()
+                }
+                "dot" => {
+                    let mut dot = Vec::new();
+                    net.to_dot(&mut dot)
+                        .map_err(|err| format!("unable to generate dot: {:?}", err))?;
+                    spans::annotate_dot(&String::from_utf8_lossy(&dot), &locations).into_bytes()
+                }
+                "spans" => serde_json::to_vec_pretty(&locations)
+                    .map_err(|err| format!("unable to generate spans: {}", err))?,
+                _ => unreachable!("unknown format {}", format),
+            };
+            create_output(&path)?
+                .write_all(&content)
+                .map_err(|err| format!("unable to write {}: {}", path.display(), err))?;
+        }
+        Ok(())
+    }
+}
+
 pub fn main() {
     init::init_early_loggers();
+    // `granite explain` reads the results of lola instead of compiling a crate
+    if std::env::args().nth(1).as_deref() == Some("explain") {
+        if let Err(msg) = explain::run(std::env::args().skip(1).collect()) {
+            eprintln!("error: {}", msg);
+            std::process::exit(1);
+        }
+        return;
+    }
     let matches = clap::App::new("granite")
         .version("0.1")
         .author("Tom Meyer <tom.meyer89@gmail.com>")
@@ -82,15 +283,115 @@ pub fn main() {
             Arg::with_name("output_format")
                 .long("format")
                 .value_name("FORMAT")
-                .help("Defines the output standard for the generated petri net")
-                .possible_values(&["pnml", "lola", "dot"])
+                .help("Defines the output standard for the generated petri net, `spans` maps its nodes to source locations")
+                .possible_values(FORMATS)
                 .multiple(true)
                 .default_value("pnml"),
         )
         .arg(
             Arg::with_name("mir_dump")
                 .long("mir_dump")
-                .help("Dumps pretty printed mir into the given file")
+                .value_name("FILE")
+                .help("Dumps pretty printed mir into the given file (`-` for stdout), defaults to a `.mir` file next to the net")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("out_dir")
+                .long("out-dir")
+                .value_name("DIR")
+                .help("The directory the outputs are written to")
+                .default_value("."),
+        )
+        .arg(
+            Arg::with_name("output_prefix")
+                .long("output-prefix")
+                .value_name("PREFIX")
+                .help("The file name of the outputs without extension, defaults to `net` or `<crate>_<entry>` for separate entries")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .value_name("FORMAT=PATH")
+                .help("Writes a format to the given path instead of the output directory (`-` for stdout)")
+                .multiple(true)
+                .number_of_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("spurious_wakeups")
+                .long("spurious-wakeups")
+                .help("Allows condition variables to wake up without being notified")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("recursion_depth")
+                .long("recursion-depth")
+                .value_name("DEPTH")
+                .help("How often recursive functions are unrolled before the recursion is cut off")
+                .validator(is_count)
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("function_summaries")
+                .long("summaries")
+                .help(
+                    "Translates every function once per set of primitives its arguments are \
+                     linked to and shares the subnet between these calls",
+                )
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("lock_api")
+                .long("lock-api")
+                .value_name("FILE")
+                .help("A toml file that maps additional lock types and functions onto mutexes")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("field_depth")
+                .long("field-depth")
+                .value_name("DEPTH")
+                .help("How many nested struct and tuple fields get their own places (0 disables fields)")
+                .validator(is_count)
+                .default_value("2"),
+        )
+        .arg(
+            Arg::with_name("loop_instances")
+                .long("loop-instances")
+                .value_name("COUNT")
+                .help("How many mutexes an allocation site inside a loop stands for")
+                .validator(is_count)
+                .default_value("2"),
+        )
+        .arg(
+            Arg::with_name("entry")
+                .long("entry")
+                .value_name("PATH")
+                .help("The path of the function to start the analysis from (e.g. `module::function`)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("entries")
+                .long("entries")
+                .value_name("MODE")
+                .help("Analyzes the main function, every public function or every test function (requires `--test`)")
+                .possible_values(&["main", "pub", "tests"])
+                .default_value("main"),
+        )
+        .arg(
+            Arg::with_name("combined")
+                .long("combined")
+                .help("Translates multiple entries into one net that starts one of them")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("ownership")
+                .long("ownership")
+                .help("Tracks which locals own a value, values are moved between locals")
                 .required(false),
         );
     let (mut rustc_args, mut granite_args) = init::parse_arguments();
@@ -108,11 +409,30 @@ pub fn main() {
     std::process::exit(result.is_err() as i32);
 }
 
-fn out_file(format: &str) -> std::fs::File {
-    match std::fs::File::create(format!("net.{}", format)) {
-        Ok(file) => file,
-        Err(err) => {
-            panic!("Unable to create file: {}", err);
-        }
+/// Checks that a numeric argument is a non-negative integer.
+fn is_count(value: String) -> Result<(), String> {
+    value
+        .parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a non-negative integer", value))
+}
+
+/// The file extension of an output format.
+fn extension(format: &str) -> &str {
+    match format {
+        // a map from node ids to source locations
+        "spans" => "spans.json",
+        format => format,
+    }
+}
+
+/// Opens a file for writing, `-` is the standard output.
+fn create_output(path: &Path) -> Result<Box<dyn Write>, String> {
+    if path == Path::new("-") {
+        return Ok(Box::new(io::stdout()));
+    }
+    match File::create(path) {
+        Ok(file) => Ok(Box::new(file)),
+        Err(err) => Err(format!("unable to create {}: {}", path.display(), err)),
     }
 }
@@ -1,5 +1,8 @@
-use crate::petri_net::function::{op_to_data_node, place_to_data_node, VirtualMemory};
-use petri_to_star::{NodeRef, PetriNet, Result};
+use crate::petri_net::function::{
+    op_to_data_node, place_to_data_node, place_to_value, Value, VirtualMemory,
+};
+use crate::petri_net::net::Net;
+use petri_to_star::{NodeRef, Result};
 use rustc::mir;
 use std::clone::Clone;
 
@@ -13,11 +16,12 @@ pub struct BasicBlock {
 #[derive(Clone, Debug)]
 pub struct Statement {
     start_place: NodeRef,
-    stmt_transition: NodeRef,
+    // alternatives for assignments to an empty or an owned local
+    stmt_transitions: Vec<NodeRef>,
 }
 
 impl BasicBlock {
-    pub fn new<'net>(net: &'net mut PetriNet, start_place: NodeRef) -> Result<Self> {
+    pub fn new<'net>(net: &'net mut Net, start_place: NodeRef) -> Result<Self> {
         let end_place = net.add_place();
         let statements = Vec::new();
         Ok(BasicBlock {
@@ -29,7 +33,7 @@ impl BasicBlock {
 
     pub fn add_statement<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         statement: &mir::Statement<'_>,
         virt_memory: &VirtualMemory,
     ) -> Result<()> {
@@ -38,7 +42,7 @@ impl BasicBlock {
         let start_place = {
             if let Some(statement) = self.statements.last() {
                 let place = net.add_place();
-                net.add_arc(statement.stmt_transition, place)?;
+                statement.connect(net, place)?;
                 place
             } else {
                 self.start_place()
@@ -50,9 +54,9 @@ impl BasicBlock {
         Ok(())
     }
 
-    pub fn finish_statement_block(&self, net: &mut PetriNet) -> Result<()> {
+    pub fn finish_statement_block(&self, net: &mut Net) -> Result<()> {
         if let Some(statement) = self.statements.last() {
-            net.add_arc(statement.stmt_transition, self.end_place)?;
+            statement.connect(net, self.end_place)?;
         } else {
             // if there is only a terminator (no statement) we have to connect start and end place of the block
             let t = net.add_transition();
@@ -73,66 +77,116 @@ impl BasicBlock {
 
 impl Statement {
     pub fn new<'net>(
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         start_place: NodeRef,
         statement: &mir::Statement<'_>,
         virt_memory: &VirtualMemory,
     ) -> Result<Self> {
+        use mir::StatementKind;
         // the statement transition is its important part
         // it "executes" the effect of the statement
         let stmt_transition = net.add_transition();
         stmt_transition.name(net, format!("{:?}", statement.kind))?;
         //stmt_transition.name(net, "");
         net.add_arc(start_place, stmt_transition)?;
+        let mut stmt_transitions = vec![stmt_transition];
+        // the assigned local owns a value afterwards, whether it had one before or not
+        let assigned = match &statement.kind {
+            StatementKind::Assign(box (lvalue, _)) => place_to_value(lvalue, virt_memory),
+            StatementKind::SetDiscriminant { place, .. } => place_to_value(place, virt_memory),
+            _ => None,
+        };
+        if let Some(value) = assigned {
+            let overwrite = net.add_transition();
+            overwrite.name(net, format!("{:?} overwrite", statement.kind))?;
+            net.add_arc(start_place, overwrite)?;
+            net.add_arc(value.empty_place, stmt_transition)?;
+            net.add_arc(value.owned_place, overwrite)?;
+            net.add_arc(stmt_transition, value.owned_place)?;
+            net.add_arc(overwrite, value.owned_place)?;
+            stmt_transitions.push(overwrite);
+        }
         let stmt = Statement {
             start_place: start_place.clone(),
-            stmt_transition,
+            stmt_transitions,
         };
-        stmt.build(net, statement, virt_memory)?;
+        for stmt_transition in &stmt.stmt_transitions {
+            stmt.build(net, statement, virt_memory, *stmt_transition)?;
+        }
         Ok(stmt)
     }
     pub fn start_place(&self) -> &NodeRef {
         &self.start_place
     }
 
+    /// The start place and the transitions of all alternatives.
+    pub fn nodes(&self) -> Vec<NodeRef> {
+        let mut nodes = vec![self.start_place];
+        nodes.extend(self.stmt_transitions.iter().copied());
+        nodes
+    }
+
+    /// Continues the flow of all alternatives of the statement in `place`.
+    fn connect(&self, net: &mut Net, place: NodeRef) -> Result<()> {
+        for stmt_transition in &self.stmt_transitions {
+            net.add_arc(*stmt_transition, place)?;
+        }
+        Ok(())
+    }
+
     fn build<'net>(
         &self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         statement: &mir::Statement<'_>,
         virt_memory: &VirtualMemory,
+        stmt_transition: NodeRef,
     ) -> Result<()> {
         use mir::StatementKind;
         match &statement.kind {
             StatementKind::Assign(box (lvalue, rvalue)) => {
-                self.build_assign(net, virt_memory, lvalue, rvalue)?
+                self.build_assign(net, virt_memory, lvalue, rvalue, stmt_transition)?
             }
             StatementKind::StorageLive(local) => {
                 let local = virt_memory.get_local(&local).expect("local not found");
-                net.add_arc(
-                    local.prenatal_place.expect("no uninitialized place"),
-                    self.stmt_transition,
-                )?;
-                net.add_arc(self.stmt_transition, local.live_place)?;
+                match local.prenatal_place {
+                    Some(prenatal_place) => net.add_arc(prenatal_place, stmt_transition)?,
+                    // untracked storage
+                    None => net.add_arc(local.live_place, stmt_transition)?,
+                }
+                net.add_arc(stmt_transition, local.live_place)?;
             }
             StatementKind::StorageDead(local) => {
                 let local = virt_memory.get_local(&local).expect("local not found");
-                net.add_arc(local.live_place, self.stmt_transition)?;
-                net.add_arc(
-                    self.stmt_transition,
-                    local.dead_place.expect("no dead place"),
-                )?;
+                net.add_arc(local.live_place, stmt_transition)?;
+                match local.dead_place {
+                    Some(dead_place) => net.add_arc(stmt_transition, dead_place)?,
+                    // untracked storage
+                    None => net.add_arc(stmt_transition, local.live_place)?,
+                }
             }
             StatementKind::SetDiscriminant { place, .. } => {
                 let place_node = place_to_data_node(place, virt_memory);
-                net.add_arc(place_node, self.stmt_transition)?;
-                net.add_arc(self.stmt_transition, place_node)?;
+                net.add_arc(place_node, stmt_transition)?;
+                net.add_arc(stmt_transition, place_node)?;
+            }
+            // only a marker for the borrow checker
+            StatementKind::FakeRead(_, place) => {
+                let place_node = place_to_data_node(place, virt_memory);
+                add_node_to_statement(net, place_node, stmt_transition)?;
             }
-            StatementKind::FakeRead(_, _)
-            | StatementKind::InlineAsm(_)
-            | StatementKind::Retag(_, _)
-            | StatementKind::AscribeUserType(box (_, _), _) => {
-                panic!("statementKind not supported: {:?}", statement.kind)
+            // the effect of inline assembly is unknown, so it may access all operands
+            StatementKind::InlineAsm(asm) => {
+                for output in asm.outputs.iter() {
+                    let output_node = place_to_data_node(output, virt_memory);
+                    add_node_to_statement(net, output_node, stmt_transition)?;
+                }
+                for (_, input) in asm.inputs.iter() {
+                    let input_node = op_to_data_node(input, virt_memory);
+                    add_node_to_statement(net, input_node, stmt_transition)?;
+                }
             }
+            // no runtime semantics
+            StatementKind::Retag(_, _) | StatementKind::AscribeUserType(box (_, _), _) => {}
             StatementKind::Nop => {}
         }
         Ok(())
@@ -140,65 +194,101 @@ impl Statement {
 
     fn build_assign<'net>(
         &self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         virt_memory: &VirtualMemory,
         lvalue: &mir::Place<'_>,
         rvalue: &mir::Rvalue<'_>,
+        stmt_transition: NodeRef,
     ) -> Result<()> {
         use mir::Rvalue;
         let llocal = place_to_data_node(lvalue, virt_memory);
-        add_node_to_statement(net, llocal, self.stmt_transition)?;
+        add_node_to_statement(net, llocal, stmt_transition)?;
         match rvalue {
             Rvalue::Use(ref operand)
             | Rvalue::Repeat(ref operand, _)
             | Rvalue::UnaryOp(_, ref operand) => {
                 let op_place = op_to_data_node(operand, virt_memory);
-                add_node_to_statement(net, op_place, self.stmt_transition)?;
+                add_node_to_statement(net, op_place, stmt_transition)?;
+                use_value(net, operand, virt_memory, stmt_transition)?;
             }
+            // borrows need the value, but do not take it
             Rvalue::Ref(_, _, ref place) | Rvalue::Len(ref place) => {
                 let place_local = place_to_data_node(place, virt_memory);
-                add_node_to_statement(net, place_local, self.stmt_transition)?;
+                add_node_to_statement(net, place_local, stmt_transition)?;
+                if let Some(value) = place_to_value(place, virt_memory) {
+                    add_node_to_statement(net, value.owned_place, stmt_transition)?;
+                }
             }
             Rvalue::Cast(ref _kind, ref operand, ref _typ) => {
                 let op_place = op_to_data_node(operand, virt_memory);
-                add_node_to_statement(net, op_place, self.stmt_transition)?;
+                add_node_to_statement(net, op_place, stmt_transition)?;
+                use_value(net, operand, virt_memory, stmt_transition)?;
             }
             Rvalue::BinaryOp(ref _operator, ref loperand, ref roperand)
             | Rvalue::CheckedBinaryOp(ref _operator, ref loperand, ref roperand) => {
                 let lop_place = op_to_data_node(loperand, virt_memory);
                 let rop_place = op_to_data_node(roperand, virt_memory);
-                add_node_to_statement(net, lop_place, self.stmt_transition)?;
-                add_node_to_statement(net, rop_place, self.stmt_transition)?;
+                add_node_to_statement(net, lop_place, stmt_transition)?;
+                add_node_to_statement(net, rop_place, stmt_transition)?;
+                use_value(net, loperand, virt_memory, stmt_transition)?;
+                use_value(net, roperand, virt_memory, stmt_transition)?;
             }
             Rvalue::NullaryOp(ref operator, ref _typ) => match operator {
                 // these are essentially a lookup of the type size in the static space
                 mir::NullOp::SizeOf | mir::NullOp::Box => {
-                    net.add_arc(virt_memory.get_constant(), self.stmt_transition)?;
-                    net.add_arc(self.stmt_transition, virt_memory.get_constant())?;
+                    net.add_arc(virt_memory.get_constant(), stmt_transition)?;
+                    net.add_arc(stmt_transition, virt_memory.get_constant())?;
                 }
             },
             Rvalue::Discriminant(ref place) => {
                 let op_place = place_to_data_node(place, virt_memory);
-                add_node_to_statement(net, op_place, self.stmt_transition)?;
+                add_node_to_statement(net, op_place, stmt_transition)?;
             }
             Rvalue::Aggregate(ref _kind, ref operands) => {
                 //FIXME: does the kind matter?
                 for operand in operands {
                     let op_place = op_to_data_node(operand, virt_memory);
-                    add_node_to_statement(net, op_place, self.stmt_transition)?;
+                    add_node_to_statement(net, op_place, stmt_transition)?;
+                    use_value(net, operand, virt_memory, stmt_transition)?;
                 }
             }
             Rvalue::AddressOf(_, place) => {
                 let place_local = place_to_data_node(place, virt_memory);
-                add_node_to_statement(net, place_local, self.stmt_transition)?;
+                add_node_to_statement(net, place_local, stmt_transition)?;
             }
         }
         Ok(())
     }
 }
 
+/// Moving an operand takes the value of its local, copying only reads it.
+/// Moving out of a part of a local leaves the rest of it owned.
+fn use_value(
+    net: &mut Net,
+    operand: &mir::Operand<'_>,
+    virt_memory: &VirtualMemory,
+    stmt_transition: NodeRef,
+) -> Result<()> {
+    let (place, is_move) = match operand {
+        mir::Operand::Move(place) => (place, true),
+        mir::Operand::Copy(place) => (place, false),
+        mir::Operand::Constant(_) => return Ok(()),
+    };
+    let value: Value = match place_to_value(place, virt_memory) {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    if is_move && place.projection.is_empty() {
+        net.add_arc(value.owned_place, stmt_transition)?;
+        net.add_arc(stmt_transition, value.empty_place)?;
+    } else {
+        add_node_to_statement(net, value.owned_place, stmt_transition)?;
+    }
+    Ok(())
+}
+
 fn add_node_to_statement(
-    net: &mut PetriNet,
+    net: &mut Net,
     place_node: NodeRef,
     statement_transition: NodeRef,
 ) -> Result<()> {
@@ -1,10 +1,12 @@
 use super::basic_block::BasicBlock;
-use super::intrinsics::generic_foreign;
-use super::unique_functions::MutexList;
-use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use super::intrinsics::{atomic_compare_exchange_foreign, generic_foreign};
+use super::net::Net;
+use super::unique_functions::Outcome;
+use petri_to_star::{NodeRef, PlaceRef, Result};
 use rustc::mir;
+use rustc_hir::def_id::DefId;
 use rustc_index::vec::IndexVec;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
 macro_rules! active_block {
@@ -53,10 +55,32 @@ pub struct VirtualMemory {
     // the DefId can be of an entire function and
     // inlining may split the same static into different DefIds
     statics: HashMap<mir::Promoted, Data>,
+    // static items are shared by all functions
+    static_items: HashMap<DefId, Local>,
+    // the known parts of locals, see `FieldMemory`
+    fields: HashMap<(Local, FieldPath), Local>,
     // constants currently don't need special data and can be represented all with the same node
     constants: Data,
 }
 
+/// A step from a local into one of its parts.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum FieldStep {
+    Field(usize),
+    Variant(usize),
+}
+
+pub type FieldPath = Vec<FieldStep>;
+
+/// The parts of locals that get their own places.
+/// Fields belong to the petri net local they are part of and are shared by all functions
+/// (like the locals passed as arguments).
+#[derive(Debug)]
+pub struct FieldMemory {
+    fields: HashMap<(Local, FieldPath), Local>,
+    keys: HashMap<Local, (Local, FieldPath)>,
+}
+
 #[derive(Debug)]
 pub struct Function<'mir> {
     pub name: String,
@@ -66,6 +90,8 @@ pub struct Function<'mir> {
     pub active_block: Option<mir::BasicBlock>,
     start_place: NodeRef,
     return_flow: NodeRef,
+    // where calls continue after the return value is assigned, see `return_flow`
+    return_places: HashMap<mir::BasicBlock, NodeRef>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,6 +106,15 @@ pub struct Local {
     pub(crate) prenatal_place: Option<NodeRef>, // function arguments can be constant
     pub(crate) live_place: NodeRef,
     pub(crate) dead_place: Option<NodeRef>,
+    pub(crate) value: Option<Value>, // only with the ownership model
+}
+
+/// Whether a local owns a value. Values move between locals,
+/// so a local that was moved out of is empty until it is assigned again.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Value {
+    pub(crate) owned_place: NodeRef,
+    pub(crate) empty_place: NodeRef,
 }
 
 #[derive(Debug, Clone, Hash)]
@@ -90,7 +125,7 @@ pub enum LocalKey {
 }
 
 impl Local {
-    pub fn new<'net>(net: &'net mut PetriNet, name: &str) -> Result<Self> {
+    pub fn new<'net>(net: &'net mut Net, name: &str) -> Result<Self> {
         let prenatal_place = Some(net.add_place());
         PlaceRef::try_from(prenatal_place.unwrap())?.marking(net, 1)?;
         let live_place = net.add_place();
@@ -106,13 +141,53 @@ impl Local {
             prenatal_place,
             live_place,
             dead_place,
+            value: None,
         })
     }
+
+    /// A local that additionally tracks if it owns a value.
+    pub fn new_owned<'net>(net: &'net mut Net, name: &str) -> Result<Self> {
+        let mut local = Local::new(net, name)?;
+        local.add_value(net, name, false)?;
+        Ok(local)
+    }
+
+    /// Tracks the value of the local. An `owned` local holds a value from the start,
+    /// e.g. an argument that is passed in by code that is not translated.
+    pub fn add_value<'net>(&mut self, net: &'net mut Net, name: &str, owned: bool) -> Result<()> {
+        let owned_place = net.add_place();
+        owned_place.name(net, format!("{} owned", name))?;
+        let empty_place = net.add_place();
+        empty_place.name(net, format!("{} empty", name))?;
+        let initial = if owned { owned_place } else { empty_place };
+        PlaceRef::try_from(initial)?.marking(net, 1)?;
+        self.value = Some(Value {
+            owned_place,
+            empty_place,
+        });
+        Ok(())
+    }
+    /// A local without uninitialized and dead places.
+    /// Storage statements only read the live place, so the local can be used
+    /// by multiple activations of the same function.
+    pub fn new_untracked<'net>(net: &'net mut Net, name: &str) -> Result<Self> {
+        let live_place = net.add_place();
+        live_place.name(net, format!("{} live", name))?;
+        PlaceRef::try_from(live_place)?.marking(net, 1)?;
+        Ok(Local {
+            prenatal_place: None,
+            live_place,
+            dead_place: None,
+            value: None,
+        })
+    }
+
     pub fn new_constant(live_place: NodeRef) -> Self {
         Self {
             prenatal_place: None,
             live_place,
             dead_place: None,
+            value: None,
         }
     }
 }
@@ -142,6 +217,20 @@ impl VirtualMemory {
         }
     }
 
+    pub fn get_static_item(&self, def_id: &DefId) -> Option<&Local> {
+        self.static_items.get(def_id)
+    }
+
+    /// The most specific known part of the local on the given path.
+    pub fn get_field(&self, base: Local, path: &[FieldStep]) -> Local {
+        for length in (1..=path.len()).rev() {
+            if let Some(field) = self.fields.get(&(base, path[..length].to_vec())) {
+                return *field;
+            }
+        }
+        base
+    }
+
     pub fn get_constant(&self) -> NodeRef {
         match &self.constants {
             Data::Constant(constant) => *constant,
@@ -154,15 +243,15 @@ impl<'mir> Function<'mir> {
     pub fn new<'net>(
         name: String,
         mir_body: &'mir mir::BodyAndCache<'mir>,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         mut args: Vec<Local>, // data that is used from the previous stack frame
         data_return: Local,   // node which stores the return value
         start_place: NodeRef, // where to start from
         constant_memory: &Data,
         static_memory: &HashMap<mir::Promoted, Data>,
+        static_items: &HashMap<DefId, Local>,
         return_flow: NodeRef, // where to continue after the call
-        mutex_list: &mut MutexList,
-        tcx: rustc::ty::TyCtxt<'mir>,
+        ownership: bool,      // track the values of the locals
     ) -> Result<Self> {
         let mut function = Function {
             name,
@@ -173,32 +262,57 @@ impl<'mir> Function<'mir> {
                 locals: HashMap::new(),
                 constants: constant_memory.clone(),
                 statics: static_memory.clone(),
+                static_items: static_items.clone(),
+                fields: HashMap::new(),
             },
             active_block: None,
             start_place,
             return_flow,
+            return_places: HashMap::new(),
         };
         // add the locals but remember the locals from the previous stack frame
         // index zero is the return local followed by the function arguments
         args.insert(0, data_return);
-        function.add_locals(net, &function.mir_body.local_decls, args, mutex_list, tcx)?;
+        function.add_locals(net, &function.mir_body.local_decls, args, ownership)?;
         Ok(function)
     }
 
     pub fn add_statement<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         statement: &mir::Statement<'_>,
-    ) -> Result<()> {
-        active_block_mut!(self).add_statement(net, statement, &self.virt_memory)?;
-        Ok(())
+    ) -> Result<Vec<NodeRef>> {
+        let block = active_block_mut!(self);
+        block.add_statement(net, statement, &self.virt_memory)?;
+        Ok(block
+            .statements
+            .last()
+            .expect("statement was not added")
+            .nodes())
     }
 
-    pub fn finish_basic_block(&self, net: &mut PetriNet) -> Result<()> {
+    pub fn finish_basic_block(&self, net: &mut Net) -> Result<()> {
         active_block!(self).finish_statement_block(net)
     }
 
-    pub fn goto<'net>(&mut self, net: &'net mut PetriNet, to: mir::BasicBlock) -> Result<()> {
+    /// Whether the block can be reached again from its successors.
+    pub fn in_loop(&self, block: mir::BasicBlock) -> bool {
+        let blocks = self.mir_body.basic_blocks();
+        let mut visited = HashSet::new();
+        let mut pending: Vec<mir::BasicBlock> =
+            blocks[block].terminator().successors().copied().collect();
+        while let Some(next) = pending.pop() {
+            if next == block {
+                return true;
+            }
+            if visited.insert(next) {
+                pending.extend(blocks[next].terminator().successors().copied());
+            }
+        }
+        false
+    }
+
+    pub fn goto<'net>(&mut self, net: &'net mut Net, to: mir::BasicBlock) -> Result<()> {
         let t = net.add_transition();
         t.name(net, "Goto".into())?;
         net.add_arc(active_block!(self).end_place(), t)?;
@@ -207,7 +321,7 @@ impl<'mir> Function<'mir> {
         Ok(())
     }
 
-    pub fn retorn<'net>(&mut self, net: &'net mut PetriNet) -> Result<()> {
+    pub fn retorn<'net>(&mut self, net: &'net mut Net) -> Result<()> {
         let source = {
             // check if we got trolled by an empty function
             if let Some(mir_block) = self.active_block {
@@ -227,11 +341,13 @@ impl<'mir> Function<'mir> {
         Ok(())
     }
 
+    /// Returns the transitions into the targets in the order of the targets.
     pub fn switch_int<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         targets: &Vec<mir::BasicBlock>,
-    ) -> Result<()> {
+    ) -> Result<Vec<NodeRef>> {
+        let mut transitions = Vec::new();
         for bb in targets {
             if !self.basic_blocks.contains_key(bb) {
                 self.add_basic_block(net, *bb)?;
@@ -242,11 +358,12 @@ impl<'mir> Function<'mir> {
             connection_transition.name(net, format!("switch int{}", bb.index()))?;
             net.add_arc(source_end, connection_transition)?;
             net.add_arc(connection_transition, target_start)?;
+            transitions.push(connection_transition);
         }
-        Ok(())
+        Ok(transitions)
     }
 
-    pub fn resume<'net>(&mut self, net: &'net mut PetriNet, unwind_place: NodeRef) -> Result<()> {
+    pub fn resume<'net>(&mut self, net: &'net mut Net, unwind_place: NodeRef) -> Result<()> {
         // TODO: make the unwind and resume semantic clear
         let source_place = active_block!(self).end_place();
         let t = net.add_transition();
@@ -256,32 +373,71 @@ impl<'mir> Function<'mir> {
         Ok(())
     }
 
+    /// Returns the drop transitions (the regular one and, if present, the unwinding one)
+    /// so that the caller can attach the effects of the dropped value.
+    /// If the ownership of the dropped local is tracked, the returned transitions
+    /// consume its value and additional transitions skip the drop of a moved value.
     pub fn drop<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         target: mir::BasicBlock,
         unwind: Option<mir::BasicBlock>,
-    ) -> Result<()> {
-        let target_start = block_to_start_place!(self, net, target);
+        dropped: Option<Value>,
+    ) -> Result<Vec<NodeRef>> {
+        let mut targets = vec![(target, "drop")];
+        if let Some(unwind) = unwind {
+            targets.push((unwind, "drop_unwind"));
+        }
         let source = active_block!(self).end_place().clone();
-        let t = net.add_transition();
-        t.name(net, "drop".into())?;
-        net.add_arc(source, t)?;
-        net.add_arc(t, target_start)?;
+        let mut transitions = Vec::new();
+        for (block, name) in targets {
+            let block_start = block_to_start_place!(self, net, block);
+            let t = net.add_transition();
+            t.name(net, name.into())?;
+            net.add_arc(source, t)?;
+            net.add_arc(t, block_start)?;
+            transitions.push(t);
+            if let Some(value) = dropped {
+                net.add_arc(value.owned_place, t)?;
+                net.add_arc(t, value.empty_place)?;
+                let t_moved = net.add_transition();
+                t_moved.name(net, format!("{} moved", name))?;
+                net.add_arc(source, t_moved)?;
+                net.add_arc(t_moved, block_start)?;
+                net.add_arc(value.empty_place, t_moved)?;
+                net.add_arc(t_moved, value.empty_place)?;
+            }
+        }
+        Ok(transitions)
+    }
 
-        if let Some(unwind) = unwind {
-            let unwind_start = block_to_start_place!(self, net, unwind);
-            let t_unwind = net.add_transition();
-            t_unwind.name(net, "drop_unwind".into())?;
-            net.add_arc(source, t_unwind)?;
-            net.add_arc(t_unwind, unwind_start)?;
+    /// Where a call continues after it returned into `destination`.
+    /// If the destination owns its value afterwards, the assignment happens here
+    /// (also for calls that are emulated and do not assign the return value themselves).
+    pub fn return_flow<'net>(
+        &mut self,
+        net: &'net mut Net,
+        destination: &(mir::Place<'_>, mir::BasicBlock),
+    ) -> Result<NodeRef> {
+        let (place, block) = destination;
+        let block_start = block_to_start_place!(self, net, *block);
+        let value = match place_to_value(place, &self.virt_memory) {
+            Some(value) => value,
+            None => return Ok(block_start),
         };
-        Ok(())
+        if let Some(return_place) = self.return_places.get(block) {
+            return Ok(*return_place);
+        }
+        let return_place = net.add_place();
+        return_place.name(net, format!("bb {} returned", block.index()))?;
+        assign_value(net, value, return_place, block_start, "return value")?;
+        self.return_places.insert(*block, return_place);
+        Ok(return_place)
     }
 
     pub fn assert(
         &mut self,
-        net: &mut PetriNet,
+        net: &mut Net,
         condition: &mir::Operand<'_>,
         _expected: bool,
         target: mir::BasicBlock,
@@ -311,17 +467,18 @@ impl<'mir> Function<'mir> {
 
     pub fn emulate_foreign(
         &mut self,
-        net: &mut PetriNet,
+        net: &mut Net,
         intrinsic_name: &str,
         //TODO: check arguments -> are noderefs needed?
         args: &Vec<mir::Operand<'_>>,
         destination: &Option<(mir::Place<'_>, mir::BasicBlock)>,
         cleanup: Option<mir::BasicBlock>,
         panic_place: NodeRef,
+        outcome: Option<&Outcome>,
     ) -> Result<()> {
-        if let Some((destination_node, destination_block)) = destination {
-            let node = place_to_data_node(destination_node, &self.virt_memory).clone();
-            let block = block_to_start_place!(self, net, *destination_block);
+        if let Some(destination) = destination {
+            let node = place_to_data_node(&destination.0, &self.virt_memory).clone();
+            let block = self.return_flow(net, destination)?;
 
             let cleanup = match cleanup {
                 Some(block) => Some(block_to_start_place!(self, net, block)),
@@ -343,14 +500,27 @@ impl<'mir> Function<'mir> {
                 | name.contains("std::intrinsics::min_align_of_val")
                 | name.contains("std::intrinsics::caller_location")
                 | name.contains("std::intrinsics::size_of_val")
-                //TODO: atomic functions need to be explained
-                | name.contains("std::intrinsics::atomic_load_acq")
-                | name.contains("std::intrinsics::atomic_load_relaxed")
-                | name.contains("std::intrinsics::atomic_load")
                 | name.contains("std::intrinsics::transmute") =>
                 {
                     generic_foreign(net, &arg_nodes, source, node, block, cleanup, name.into())?
                 }
+                // atomic objects of the std api are handled as unique functions
+                // these are the remaining direct uses (e.g. in std internals)
+                name if name.contains("std::intrinsics::atomic_cxchg") => {
+                    atomic_compare_exchange_foreign(
+                        net,
+                        &arg_nodes,
+                        source,
+                        node,
+                        block,
+                        cleanup,
+                        name.into(),
+                        outcome.expect("no outcome for compare exchange"),
+                    )?
+                }
+                name if name.contains("std::intrinsics::atomic_") => {
+                    generic_foreign(net, &arg_nodes, source, node, block, cleanup, name.into())?
+                }
                 name if name.contains("libc::unix::pthread_mutexattr_init")
                     | name.contains("libc::unix::pthread_mutex_init")
                     | name.contains("libc::unix::pthread_mutexattr_settype")
@@ -391,7 +561,7 @@ impl<'mir> Function<'mir> {
         Ok(())
     }
 
-    pub fn handle_panic(&mut self, net: &mut PetriNet, panic_place: NodeRef) -> Result<()> {
+    pub fn handle_panic(&mut self, net: &mut Net, panic_place: NodeRef) -> Result<()> {
         let source = active_block!(self).end_place().clone();
         let t = net.add_transition();
         t.name(net, "panic".into())?;
@@ -402,7 +572,7 @@ impl<'mir> Function<'mir> {
 
     pub fn activate_block<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         block: mir::BasicBlock,
     ) -> Result<()> {
         match self.basic_blocks.get(&block) {
@@ -430,7 +600,7 @@ impl<'mir> Function<'mir> {
 
     fn add_basic_block<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         block: mir::BasicBlock,
     ) -> Result<&BasicBlock> {
         let start_place = if self.basic_blocks.is_empty() {
@@ -452,7 +622,7 @@ impl<'mir> Function<'mir> {
 
     pub fn get_basic_block_start(
         &mut self,
-        net: &mut PetriNet,
+        net: &mut Net,
         block: mir::BasicBlock,
     ) -> Result<NodeRef> {
         Ok(block_to_start_place!(self, net, block))
@@ -460,11 +630,10 @@ impl<'mir> Function<'mir> {
 
     fn add_locals<'net, 'tcx>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         locals: &IndexVec<mir::Local, mir::LocalDecl<'tcx>>,
         known_locals: Vec<Local>,
-        mutex_list: &mut MutexList,
-        tcx: rustc::ty::TyCtxt<'tcx>,
+        ownership: bool,
     ) -> Result<()> {
         // a lot of locals here:
         // mir_local: mir::Local => index for local decls in mir data structure
@@ -474,29 +643,14 @@ impl<'mir> Function<'mir> {
             let name = format!("{}_{}: {}", self.name, mir_local.index(), decl.ty);
             let local = if let Some(local) = known_locals.get(mir_local.index()) {
                 *local
+            } else if ownership {
+                Local::new_owned(net, &name)?
             } else {
                 Local::new(net, &name)?
             };
             self.virt_memory
                 .locals
                 .insert(mir_local, Data::Local(local));
-
-            // check if its a mutex for deadlock detection
-            let mut type_walk = decl.ty.walk();
-            if type_walk.next().unwrap().sort_string(tcx) == "struct `std::sync::Mutex`" {
-                let mut is_generic = false;
-                for ty in type_walk {
-                    if ty.sort_string(tcx) == "type parameter `T`" {
-                        is_generic = true;
-                        break;
-                    }
-                }
-                if !is_generic {
-                    let mutex = mutex_list.add(net)?;
-                    debug!("link '{:?}' to mutex '{:?}'", mir_local, mutex);
-                    mutex_list.link(local, mutex);
-                }
-            }
         }
         Ok(())
     }
@@ -505,6 +659,11 @@ impl<'mir> Function<'mir> {
         self.virt_memory.get_local(local)
     }
 
+    /// Makes a part of a local known to this function.
+    pub fn add_field(&mut self, base: Local, path: FieldPath, field: Local) {
+        self.virt_memory.fields.insert((base, path), field);
+    }
+
     pub fn get_promoted(&self, statik: &mir::Promoted) -> Option<Local> {
         match self.virt_memory.get_static(statik) {
             Some(node) => Some(Local::new_constant(node)),
@@ -524,20 +683,95 @@ impl<'mir> Function<'mir> {
             }
         }
     }
+    pub fn place_to_value(&self, place: &mir::Place<'_>) -> Option<Value> {
+        place_to_value(place, &self.virt_memory)
+    }
+
     pub fn place_to_local(&self, place: &mir::Place<'_>) -> Local {
-        match place.local_or_deref_local() {
-            Some(local) => *self.get_local(&local).expect("local not found"),
-            None => match &place.base {
-                mir::PlaceBase::Local(local) => *self.get_local(local).expect("local not found"),
-                mir::PlaceBase::Static(statik) => match statik.kind {
-                    mir::StaticKind::Static => panic!("staticKind::Static -> cannot convert"),
-                    mir::StaticKind::Promoted(promoted, _) => self
+        let base = match &place.base {
+            mir::PlaceBase::Local(local) => *self.get_local(local).expect("local not found"),
+            mir::PlaceBase::Static(statik) => match statik.kind {
+                mir::StaticKind::Static => *self
+                    .virt_memory
+                    .get_static_item(&statik.def_id)
+                    .expect("static item not found"),
+                mir::StaticKind::Promoted(promoted, _) => {
+                    return self
                         .get_promoted(&promoted)
-                        .expect("promoted statik not found"),
-                },
+                        .expect("promoted statik not found")
+                }
             },
+        };
+        self.virt_memory.get_field(base, &field_path(place))
+    }
+}
+
+impl FieldMemory {
+    pub fn new() -> Self {
+        Self {
+            fields: HashMap::new(),
+            keys: HashMap::new(),
         }
     }
+
+    pub fn get(&self, base: Local, path: &[FieldStep]) -> Option<&Local> {
+        self.fields.get(&(base, path.to_vec()))
+    }
+
+    pub fn insert(&mut self, base: Local, path: FieldPath, field: Local) {
+        self.keys.insert(field, (base, path.clone()));
+        self.fields.insert((base, path), field);
+    }
+
+    /// The local a field is part of and the path to it.
+    /// Locals that are not part of another local have an empty path.
+    pub fn key_of(&self, local: Local) -> (Local, FieldPath) {
+        match self.keys.get(&local) {
+            Some(key) => key.clone(),
+            None => (local, Vec::new()),
+        }
+    }
+
+    /// All known parts below the given path with their remaining path.
+    pub fn descendants(&self, base: Local, prefix: &[FieldStep]) -> Vec<(FieldPath, Local)> {
+        self.fields
+            .iter()
+            .filter(|((field_base, path), _)| {
+                *field_base == base && path.len() > prefix.len() && path.starts_with(prefix)
+            })
+            .map(|((_, path), field)| (path[prefix.len()..].to_vec(), *field))
+            .collect()
+    }
+}
+
+/// The projections of a place that select a part of its local.
+/// Dereferences are skipped and indexing ends the path,
+/// because the elements of arrays and slices are not distinguished.
+/// The path is only the key of the place within the function,
+/// the translator resolves dereferences to the fields of the pointee.
+pub fn field_path(place: &mir::Place<'_>) -> FieldPath {
+    let mut path = Vec::new();
+    for elem in place.projection.iter() {
+        match elem {
+            mir::ProjectionElem::Deref => {}
+            mir::ProjectionElem::Field(field, _) => path.push(FieldStep::Field(field.index())),
+            mir::ProjectionElem::Downcast(_, variant) => {
+                path.push(FieldStep::Variant(variant.index()))
+            }
+            _ => break,
+        }
+    }
+    path
+}
+
+/// A readable suffix for the places of a field, e.g. `.0::1.2`.
+pub fn field_path_name(path: &[FieldStep]) -> String {
+    path.iter()
+        .map(|step| match step {
+            FieldStep::Field(index) => format!(".{}", index),
+            FieldStep::Variant(index) => format!("::{}", index),
+        })
+        .collect()
 }
 
 pub(crate) fn op_to_data_node(operand: &mir::Operand<'_>, memory: &VirtualMemory) -> NodeRef {
@@ -551,29 +785,49 @@ pub(crate) fn op_to_data_node(operand: &mir::Operand<'_>, memory: &VirtualMemory
 }
 
 pub(crate) fn place_to_data_node(place: &mir::Place<'_>, memory: &VirtualMemory) -> NodeRef {
-    let local = place.local_or_deref_local();
-    match local {
-        Some(local) => {
-            memory
-                .get_local(&local)
-                .expect("local not found")
-                .live_place
-        }
-        //FIXME: is it valid to just use the outermost local if nothing better was found?
-        // maybe this functions helps?
-        // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.intern_place_elems
-        // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.mk_place_elems
-        None => match &place.base {
-            mir::PlaceBase::Local(local) => {
-                memory.get_local(local).expect("local not found").live_place
-            }
-            // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.promoted_mir
-            mir::PlaceBase::Static(statik) => match statik.kind {
-                mir::StaticKind::Static => panic!("staticKind::Static -> cannot convert"),
-                mir::StaticKind::Promoted(promoted, _) => memory
+    let base = match &place.base {
+        mir::PlaceBase::Local(local) => *memory.get_local(local).expect("local not found"),
+        // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.promoted_mir
+        mir::PlaceBase::Static(statik) => match statik.kind {
+            mir::StaticKind::Static => *memory
+                .get_static_item(&statik.def_id)
+                .expect("static item not found"),
+            mir::StaticKind::Promoted(promoted, _) => {
+                return memory
                     .get_static(&promoted)
-                    .expect("promoted statik not found"),
-            },
+                    .expect("promoted statik not found")
+            }
         },
+    };
+    // parts of the local that are not known are represented by the local itself
+    memory.get_field(base, &field_path(place)).live_place
+}
+
+/// The value of the local a place belongs to, if its ownership is tracked.
+/// Parts of a local do not own values on their own.
+pub(crate) fn place_to_value(place: &mir::Place<'_>, memory: &VirtualMemory) -> Option<Value> {
+    match &place.base {
+        mir::PlaceBase::Local(local) => memory.get_local(local).expect("local not found").value,
+        mir::PlaceBase::Static(_) => None,
+    }
+}
+
+/// Assigns a value whether or not the local owned one before:
+/// the flow branches from `source` to `target` over an empty and an owned local.
+pub(crate) fn assign_value(
+    net: &mut Net,
+    value: Value,
+    source: NodeRef,
+    target: NodeRef,
+    name: &str,
+) -> Result<()> {
+    for (previous, suffix) in &[(value.empty_place, ""), (value.owned_place, " overwrite")] {
+        let t = net.add_transition();
+        t.name(net, format!("{}{}", name, suffix))?;
+        net.add_arc(source, t)?;
+        net.add_arc(t, target)?;
+        net.add_arc(*previous, t)?;
+        net.add_arc(t, value.owned_place)?;
     }
+    Ok(())
 }
@@ -1,7 +1,9 @@
-use petri_to_star::{NodeRef, PetriNet, Result};
+use super::net::Net;
+use super::unique_functions::Outcome;
+use petri_to_star::{NodeRef, Result};
 
 pub(crate) fn generic_foreign(
-    net: &mut PetriNet,
+    net: &mut Net,
     arg_nodes: &Vec<NodeRef>,
     source_node: NodeRef,
     destination_node: NodeRef, // local var that holds the return value
@@ -30,3 +32,34 @@ pub(crate) fn generic_foreign(
     }
     Ok(())
 }
+
+/// Compare-exchange intrinsics can succeed or fail.
+/// Both branches access the same data, but the control flow is split
+/// and records the outcome for the switch on the returned flag.
+pub(crate) fn atomic_compare_exchange_foreign(
+    net: &mut Net,
+    arg_nodes: &Vec<NodeRef>,
+    source_node: NodeRef,
+    destination_node: NodeRef,
+    destination_block_start: NodeRef,
+    cleanup_node: Option<NodeRef>,
+    name: String,
+    outcome: &Outcome,
+) -> Result<()> {
+    for success in vec![true, false] {
+        let name = format!("{} {}", name, if success { "success" } else { "failure" });
+        let decided = net.add_place();
+        decided.name(net, name.clone())?;
+        generic_foreign(
+            net,
+            arg_nodes,
+            source_node,
+            destination_node,
+            decided,
+            if success { cleanup_node } else { None },
+            name,
+        )?;
+        outcome.decide(net, decided, destination_block_start, success)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use petri_to_star::{NodeRef, PetriNet, Result};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+/// A `PetriNet` that remembers the ids its nodes get in the output formats
+/// and the places every transition consumes from.
+/// petri_to_star numbers places and transitions separately in the order they are added,
+/// so the ids are recorded when the nodes are created.
+#[derive(Debug)]
+pub struct Net {
+    net: PetriNet,
+    ids: HashMap<NodeRef, String>,
+    places: usize,
+    transitions: Vec<NodeRef>,
+    presets: HashMap<NodeRef, Vec<NodeRef>>,
+}
+
+impl Net {
+    pub fn new() -> Self {
+        Net {
+            net: PetriNet::new(),
+            ids: HashMap::new(),
+            places: 0,
+            transitions: Vec::new(),
+            presets: HashMap::new(),
+        }
+    }
+
+    pub fn add_place(&mut self) -> NodeRef {
+        let place = self.net.add_place();
+        self.ids.insert(place, format!("p_{}", self.places));
+        self.places += 1;
+        place
+    }
+
+    pub fn add_transition(&mut self) -> NodeRef {
+        let transition = self.net.add_transition();
+        self.ids
+            .insert(transition, format!("t_{}", self.transitions.len()));
+        self.transitions.push(transition);
+        transition
+    }
+
+    pub fn add_arc(&mut self, source: NodeRef, sink: NodeRef) -> Result<()> {
+        self.net.add_arc(source, sink)?;
+        if self.id(source).starts_with('p') {
+            self.presets
+                .entry(sink)
+                .or_insert_with(Vec::new)
+                .push(source);
+        }
+        Ok(())
+    }
+
+    /// The id of the node in the output formats, e.g. `p_3` or `t_5`.
+    pub fn id(&self, node: NodeRef) -> &str {
+        self.ids.get(&node).expect("node of another net")
+    }
+
+    /// All transitions in the order they were added.
+    pub fn transitions(&self) -> &[NodeRef] {
+        &self.transitions
+    }
+
+    /// The places the transition consumes from.
+    pub fn preset(&self, transition: NodeRef) -> &[NodeRef] {
+        self.presets
+            .get(&transition)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+}
+
+impl Deref for Net {
+    type Target = PetriNet;
+
+    fn deref(&self) -> &PetriNet {
+        &self.net
+    }
+}
+
+impl DerefMut for Net {
+    fn deref_mut(&mut self) -> &mut PetriNet {
+        &mut self.net
+    }
+}
@@ -1,15 +1,18 @@
-use log::warn;
-use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use log::{debug, warn};
+use petri_to_star::{NodeRef, PlaceRef, Result};
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
 use super::function::{Function, Local};
+use super::net::Net;
 
+/// Locals point to sets of mutexes (and guards) because the same local can refer
+/// to different mutexes, e.g. an element of a vector of mutexes.
 #[derive(Debug)]
 pub struct MutexList {
     list: Vec<Mutex>,
-    links: HashMap<Local, MutexRef>,
-    guards: HashMap<Local, MutexRef>,
+    links: HashMap<Local, Vec<MutexRef>>,
+    guards: HashMap<Local, Vec<Guard>>,
 }
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
@@ -17,6 +20,16 @@ pub struct MutexRef {
     index: usize,
 }
 
+/// A guard knows if it actually holds its mutex.
+/// The result of a failed `try_lock` is linked to a guard that never held the mutex
+/// and must not release it when dropped.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Guard {
+    mutex: MutexRef,
+    held: NodeRef,
+    free: NodeRef,
+}
+
 #[derive(Debug)]
 pub struct Mutex {
     uninitialized: NodeRef,
@@ -52,11 +65,12 @@ impl MutexList {
         }
     }
 
-    pub fn get_linked(&mut self, local: Local) -> Option<&MutexRef> {
+    /// The mutexes the local may point to.
+    pub fn get_linked(&self, local: Local) -> Option<&Vec<MutexRef>> {
         self.links.get(&local)
     }
 
-    pub fn add(&mut self, net: &mut PetriNet) -> Result<MutexRef> {
+    pub fn add(&mut self, net: &mut Net) -> Result<MutexRef> {
         let index = self.list.len();
         let uninitialized = net.add_place();
         uninitialized.name(net, format!("Mutex_{} uninitialized", index))?;
@@ -76,22 +90,1272 @@ impl MutexList {
         Ok(MutexRef { index })
     }
 
-    pub fn add_guard(&mut self, guard: Local, mutex: MutexRef) {
-        self.guards.insert(guard, mutex);
+    pub fn add_guard(&mut self, net: &mut Net, guard: Local, mutex: MutexRef) -> Result<Guard> {
+        let held = net.add_place();
+        held.name(net, format!("Mutex_{} guard held", mutex.index))?;
+        let free = net.add_place();
+        free.name(net, format!("Mutex_{} guard free", mutex.index))?;
+        PlaceRef::try_from(free)?.marking(net, 1)?;
+        let new_guard = Guard { mutex, held, free };
+        self.link_guard(guard, new_guard);
+        Ok(new_guard)
     }
 
-    pub fn is_linked(&self, local: Local) -> Option<&MutexRef> {
-        self.links.get(&local)
+    /// A mutex that is unlocked from the start, e.g. the mutex of a static item.
+    pub fn add_initialized(&mut self, net: &mut Net) -> Result<MutexRef> {
+        let mutex = self.add(net)?;
+        PlaceRef::try_from(mutex.uninitialized(self))?.marking(net, 0)?;
+        PlaceRef::try_from(mutex.unlocked(self))?.marking(net, 1)?;
+        Ok(mutex)
+    }
+
+    /// The guards the local may point to.
+    pub fn get_guards(&self, guard: Local) -> Option<&Vec<Guard>> {
+        self.guards.get(&guard)
+    }
+
+    pub fn link_guard(&mut self, local: Local, guard: Guard) {
+        let guards = self.guards.entry(local).or_insert_with(Vec::new);
+        if !guards.contains(&guard) {
+            guards.push(guard);
+        }
+    }
+
+    /// Lets the given transition move the token of the mutex from unlocked to locked
+    /// and mark the guard as holding it.
+    pub fn acquire(&self, net: &mut Net, guard: Guard, transition: NodeRef) -> Result<()> {
+        net.add_arc(guard.mutex.unlocked(self), transition)?;
+        net.add_arc(guard.free, transition)?;
+        net.add_arc(transition, guard.mutex.locked(self))?;
+        net.add_arc(transition, guard.held)?;
+        Ok(())
+    }
+
+    /// The inverse of `acquire`.
+    pub fn unlock(&self, net: &mut Net, guard: Guard, transition: NodeRef) -> Result<()> {
+        net.add_arc(guard.mutex.locked(self), transition)?;
+        net.add_arc(guard.held, transition)?;
+        net.add_arc(transition, guard.mutex.unlocked(self))?;
+        net.add_arc(transition, guard.free)?;
+        Ok(())
+    }
+
+    /// Lets the given drop transition move the token of the guarded mutex from
+    /// locked back to unlocked.
+    /// If the guard never acquired the mutex a bypass transition from `source` to `target`
+    /// drops it without effect.
+    /// A local that may point to several guards holds at most one of them. Each guard
+    /// is released by its own transition looping at `source` and the drop transition
+    /// waits until all of them are free.
+    pub fn release(
+        &self,
+        net: &mut Net,
+        guard: Local,
+        source: NodeRef,
+        transition: NodeRef,
+        target: NodeRef,
+    ) -> Result<()> {
+        match self.guards.get(&guard).map(Vec::as_slice) {
+            None | Some([]) => {}
+            Some([guard]) => {
+                debug!("release mutex '{:?}' of guard '{:?}'", guard.mutex, guard);
+                self.unlock(net, *guard, transition)?;
+
+                let bypass = net.add_transition();
+                bypass.name(net, "drop unheld guard".into())?;
+                net.add_arc(source, bypass)?;
+                net.add_arc(bypass, target)?;
+                net.add_arc(guard.free, bypass)?;
+                net.add_arc(bypass, guard.free)?;
+            }
+            Some(guards) => {
+                for guard in guards {
+                    debug!("release mutex '{:?}' of guard '{:?}'", guard.mutex, guard);
+                    let t_release = net.add_transition();
+                    t_release.name(net, format!("drop guard of Mutex_{}", guard.mutex.index))?;
+                    net.add_arc(source, t_release)?;
+                    net.add_arc(t_release, source)?;
+                    self.unlock(net, *guard, t_release)?;
+                    net.add_arc(guard.free, transition)?;
+                    net.add_arc(transition, guard.free)?;
+                }
+            }
+        }
+        Ok(())
     }
 
+    /// Adds the mutex to the points-to set of the local.
     pub fn link(&mut self, local: Local, mutex: MutexRef) {
-        match self.links.insert(local, mutex) {
+        let mutexes = self.links.entry(local).or_insert_with(Vec::new);
+        if !mutexes.contains(&mutex) {
+            mutexes.push(mutex);
+        }
+    }
+
+    /// Forgets what the local pointed to, e.g. before it is assigned again.
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+        self.guards.remove(&local);
+    }
+}
+
+#[derive(Debug)]
+pub struct ThreadList {
+    list: Vec<Thread>,
+    links: HashMap<Local, ThreadRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct ThreadRef {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct Thread {
+    start: NodeRef,
+    finished: NodeRef,
+}
+
+impl ThreadRef {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn start(&self, list: &ThreadList) -> NodeRef {
+        list.list.get(self.index).expect("thread not found").start
+    }
+    pub fn finished(&self, list: &ThreadList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("thread not found")
+            .finished
+    }
+}
+
+impl ThreadList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    /// Adds the places of a new thread. The start place is where the
+    /// translated thread body begins, the finished place gets a token
+    /// when the body returns and is consumed by `JoinHandle::join`.
+    pub fn add(&mut self, net: &mut Net) -> Result<ThreadRef> {
+        let index = self.list.len();
+        let start = net.add_place();
+        start.name(net, format!("Thread_{} start", index))?;
+        let finished = net.add_place();
+        finished.name(net, format!("Thread_{} finished", index))?;
+        self.list.push(Thread { start, finished });
+        Ok(ThreadRef { index })
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&ThreadRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, thread: ThreadRef) {
+        match self.links.insert(local, thread) {
+            None => {}
+            Some(old_thread) => {
+                if old_thread != thread {
+                    warn!("Local '{:?}' was already linked to thread '{:?}'. The old value will be overridden with thread '{:?}'", local, old_thread, thread)
+                }
+            }
+        };
+    }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+    }
+}
+
+#[derive(Debug)]
+pub struct RwLockList {
+    list: Vec<RwLock>,
+    links: HashMap<Local, RwLockRef>,
+    guards: HashMap<Local, RwGuard>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct RwLockRef {
+    index: usize,
+}
+
+/// Readers are counted with one read slot per translated `read` call.
+/// A writer needs the exclusive writer token and every read slot, so the number
+/// of concurrent readers is bounded by the number of read calls.
+/// A thread that already reads the lock can read it again at another call,
+/// like std allows it. (A waiting writer does not block new readers in the model.)
+/// Read slots are only known after the whole program is translated,
+/// so the arcs between slots and writers are added in `finalize`.
+#[derive(Debug)]
+pub struct RwLock {
+    uninitialized: NodeRef,
+    writer_free: NodeRef,
+    writing: NodeRef,
+    readers: NodeRef,
+    read_slots: Vec<NodeRef>,
+    write_acquires: Vec<NodeRef>,
+    write_releases: Vec<NodeRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum RwGuardKind {
+    Read(NodeRef), // the read slot of the read call
+    Write,
+}
+
+/// Like mutex guards, a rw guard knows if it holds the lock.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct RwGuard {
+    lock: RwLockRef,
+    kind: RwGuardKind,
+    held: NodeRef,
+    free: NodeRef,
+}
+
+impl RwLockRef {
+    pub fn uninitialized(&self, list: &RwLockList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("rwlock not found")
+            .uninitialized
+    }
+    pub fn writer_free(&self, list: &RwLockList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("rwlock not found")
+            .writer_free
+    }
+    pub fn writing(&self, list: &RwLockList) -> NodeRef {
+        list.list.get(self.index).expect("rwlock not found").writing
+    }
+    pub fn readers(&self, list: &RwLockList) -> NodeRef {
+        list.list.get(self.index).expect("rwlock not found").readers
+    }
+}
+
+impl RwLockList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+            guards: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, net: &mut Net) -> Result<RwLockRef> {
+        let index = self.list.len();
+        let uninitialized = net.add_place();
+        uninitialized.name(net, format!("RwLock_{} uninitialized", index))?;
+        PlaceRef::try_from(uninitialized)?.marking(net, 1)?;
+        let writer_free = net.add_place();
+        writer_free.name(net, format!("RwLock_{} writer free", index))?;
+        let writing = net.add_place();
+        writing.name(net, format!("RwLock_{} writing", index))?;
+        let readers = net.add_place();
+        readers.name(net, format!("RwLock_{} readers", index))?;
+        self.list.push(RwLock {
+            uninitialized,
+            writer_free,
+            writing,
+            readers,
+            read_slots: Vec::new(),
+            write_acquires: Vec::new(),
+            write_releases: Vec::new(),
+        });
+        Ok(RwLockRef { index })
+    }
+
+    /// A new read slot for a read call.
+    fn add_read_slot(&mut self, net: &mut Net, lock: RwLockRef) -> Result<NodeRef> {
+        let rw_lock = self.list.get_mut(lock.index).expect("rwlock not found");
+        let slot = net.add_place();
+        slot.name(
+            net,
+            format!(
+                "RwLock_{} read slot {}",
+                lock.index,
+                rw_lock.read_slots.len()
+            ),
+        )?;
+        PlaceRef::try_from(slot)?.marking(net, 1)?;
+        rw_lock.read_slots.push(slot);
+        Ok(slot)
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&RwLockRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, lock: RwLockRef) {
+        match self.links.insert(local, lock) {
+            None => {}
+            Some(old_lock) => {
+                if old_lock != lock {
+                    warn!("Local '{:?}' was already linked to rwlock '{:?}'. The old value will be overridden with rwlock '{:?}'", local, old_lock, lock)
+                }
+            }
+        };
+    }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+        self.guards.remove(&local);
+    }
+
+    pub fn get_guard(&self, guard: Local) -> Option<&RwGuard> {
+        self.guards.get(&guard)
+    }
+
+    pub fn link_guard(&mut self, local: Local, guard: RwGuard) {
+        self.guards.insert(local, guard);
+    }
+
+    fn add_guard(
+        &mut self,
+        net: &mut Net,
+        guard: Local,
+        lock: RwLockRef,
+        kind: RwGuardKind,
+    ) -> Result<RwGuard> {
+        let held = net.add_place();
+        held.name(net, format!("RwLock_{} guard held", lock.index))?;
+        let free = net.add_place();
+        free.name(net, format!("RwLock_{} guard free", lock.index))?;
+        PlaceRef::try_from(free)?.marking(net, 1)?;
+        let new_guard = RwGuard {
+            lock,
+            kind,
+            held,
+            free,
+        };
+        self.link_guard(guard, new_guard);
+        Ok(new_guard)
+    }
+
+    /// Lets the transition take the read slot of the call and count a reader.
+    pub fn acquire_read(
+        &mut self,
+        net: &mut Net,
+        guard: Local,
+        lock: RwLockRef,
+        transition: NodeRef,
+    ) -> Result<()> {
+        let slot = self.add_read_slot(net, lock)?;
+        let guard = self.add_guard(net, guard, lock, RwGuardKind::Read(slot))?;
+        net.add_arc(slot, transition)?;
+        net.add_arc(guard.free, transition)?;
+        net.add_arc(transition, lock.readers(self))?;
+        net.add_arc(transition, guard.held)?;
+        Ok(())
+    }
+
+    /// Lets the transition take the writer token. The read slots are added in `finalize`.
+    pub fn acquire_write(
+        &mut self,
+        net: &mut Net,
+        guard: Local,
+        lock: RwLockRef,
+        transition: NodeRef,
+    ) -> Result<()> {
+        let guard = self.add_guard(net, guard, lock, RwGuardKind::Write)?;
+        net.add_arc(lock.writer_free(self), transition)?;
+        net.add_arc(guard.free, transition)?;
+        net.add_arc(transition, lock.writing(self))?;
+        net.add_arc(transition, guard.held)?;
+        self.list
+            .get_mut(lock.index)
+            .expect("rwlock not found")
+            .write_acquires
+            .push(transition);
+        Ok(())
+    }
+
+    /// Same as `MutexList::release` for read and write guards.
+    pub fn release(
+        &mut self,
+        net: &mut Net,
+        guard: Local,
+        source: NodeRef,
+        transition: NodeRef,
+        target: NodeRef,
+    ) -> Result<()> {
+        if let Some(guard) = self.guards.get(&guard).cloned() {
+            debug!("release rwlock '{:?}' of guard '{:?}'", guard.lock, guard);
+            match guard.kind {
+                RwGuardKind::Read(slot) => {
+                    net.add_arc(guard.lock.readers(self), transition)?;
+                    net.add_arc(transition, slot)?;
+                }
+                RwGuardKind::Write => {
+                    net.add_arc(guard.lock.writing(self), transition)?;
+                    net.add_arc(transition, guard.lock.writer_free(self))?;
+                    self.list
+                        .get_mut(guard.lock.index)
+                        .expect("rwlock not found")
+                        .write_releases
+                        .push(transition);
+                }
+            }
+            net.add_arc(guard.held, transition)?;
+            net.add_arc(transition, guard.free)?;
+
+            let bypass = net.add_transition();
+            bypass.name(net, "drop unheld guard".into())?;
+            net.add_arc(source, bypass)?;
+            net.add_arc(bypass, target)?;
+            net.add_arc(guard.free, bypass)?;
+            net.add_arc(bypass, guard.free)?;
+        }
+        Ok(())
+    }
+
+    /// Connects the writers with the read slots of all read calls.
+    pub fn finalize(&self, net: &mut Net) -> Result<()> {
+        for rw_lock in &self.list {
+            for slot in &rw_lock.read_slots {
+                for acquire in &rw_lock.write_acquires {
+                    net.add_arc(*slot, *acquire)?;
+                }
+                for release in &rw_lock.write_releases {
+                    net.add_arc(*release, *slot)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct CondvarList {
+    list: Vec<Condvar>,
+    links: HashMap<Local, CondvarRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct CondvarRef {
+    index: usize,
+}
+
+/// Every thread that waits on the condvar gets its own waiter places.
+/// A notification can only be lost if no thread is waiting, which is tested
+/// with the `not_waiting` places of all waiters.
+/// Like the read slots of `RwLock`, the waiters are only known after the translation
+/// and the notifications are connected in `finalize`.
+#[derive(Debug)]
+pub struct Condvar {
+    uninitialized: NodeRef,
+    waiters: HashMap<Option<ThreadRef>, Waiter>,
+    // start and end place of notify_one calls, the end place continues the calling function
+    notify_one: Vec<(NodeRef, NodeRef)>,
+    // the place that is marked while notify_all wakes the waiters
+    notify_all: Vec<NodeRef>,
+    // transitions that are only enabled if nobody waits
+    nobody_waiting: Vec<NodeRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Waiter {
+    pub waiting: NodeRef,
+    pub not_waiting: NodeRef,
+    pub notified: NodeRef,
+}
+
+impl CondvarRef {
+    pub fn uninitialized(&self, list: &CondvarList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("condvar not found")
+            .uninitialized
+    }
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl CondvarList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, net: &mut Net) -> Result<CondvarRef> {
+        let index = self.list.len();
+        let uninitialized = net.add_place();
+        uninitialized.name(net, format!("Condvar_{} uninitialized", index))?;
+        PlaceRef::try_from(uninitialized)?.marking(net, 1)?;
+        self.list.push(Condvar {
+            uninitialized,
+            waiters: HashMap::new(),
+            notify_one: Vec::new(),
+            notify_all: Vec::new(),
+            nobody_waiting: Vec::new(),
+        });
+        Ok(CondvarRef { index })
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&CondvarRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, condvar: CondvarRef) {
+        match self.links.insert(local, condvar) {
+            None => {}
+            Some(old_condvar) => {
+                if old_condvar != condvar {
+                    warn!("Local '{:?}' was already linked to condvar '{:?}'. The old value will be overridden with condvar '{:?}'", local, old_condvar, condvar)
+                }
+            }
+        };
+    }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+    }
+
+    /// The waiter places of the given thread. `None` is the main thread.
+    pub fn waiter(
+        &mut self,
+        net: &mut Net,
+        condvar: CondvarRef,
+        thread: Option<ThreadRef>,
+    ) -> Result<Waiter> {
+        let cv = self.list.get_mut(condvar.index).expect("condvar not found");
+        if let Some(waiter) = cv.waiters.get(&thread) {
+            return Ok(*waiter);
+        }
+        let thread_name = match thread {
+            Some(thread) => format!("Thread_{}", thread.index()),
+            None => "main".into(),
+        };
+        let waiting = net.add_place();
+        waiting.name(
+            net,
+            format!("Condvar_{} {} waiting", condvar.index, thread_name),
+        )?;
+        let not_waiting = net.add_place();
+        not_waiting.name(
+            net,
+            format!("Condvar_{} {} not waiting", condvar.index, thread_name),
+        )?;
+        PlaceRef::try_from(not_waiting)?.marking(net, 1)?;
+        let notified = net.add_place();
+        notified.name(
+            net,
+            format!("Condvar_{} {} notified", condvar.index, thread_name),
+        )?;
+        let waiter = Waiter {
+            waiting,
+            not_waiting,
+            notified,
+        };
+        cv.waiters.insert(thread, waiter);
+        Ok(waiter)
+    }
+
+    /// `nobody_waiting` leads from start to end if no thread waits.
+    /// Waking a waiter is added in `finalize`.
+    pub fn add_notify_one(
+        &mut self,
+        condvar: CondvarRef,
+        start: NodeRef,
+        end: NodeRef,
+        nobody_waiting: NodeRef,
+    ) {
+        let cv = self.list.get_mut(condvar.index).expect("condvar not found");
+        cv.notify_one.push((start, end));
+        cv.nobody_waiting.push(nobody_waiting);
+    }
+
+    /// While `broadcasting` is marked all waiters are woken up.
+    /// `finished` ends the broadcast if no thread waits anymore.
+    pub fn add_notify_all(
+        &mut self,
+        condvar: CondvarRef,
+        broadcasting: NodeRef,
+        finished: NodeRef,
+    ) {
+        let cv = self.list.get_mut(condvar.index).expect("condvar not found");
+        cv.notify_all.push(broadcasting);
+        cv.nobody_waiting.push(finished);
+    }
+
+    /// Connects the notifications with the waiters of all threads.
+    pub fn finalize(&self, net: &mut Net) -> Result<()> {
+        for (index, cv) in self.list.iter().enumerate() {
+            for waiter in cv.waiters.values() {
+                for transition in &cv.nobody_waiting {
+                    net.add_arc(waiter.not_waiting, *transition)?;
+                    net.add_arc(*transition, waiter.not_waiting)?;
+                }
+                for (start, end) in &cv.notify_one {
+                    let t = net.add_transition();
+                    t.name(net, format!("Condvar_{} notify_one", index))?;
+                    net.add_arc(*start, t)?;
+                    net.add_arc(t, *end)?;
+                    Self::wake(net, waiter, t)?;
+                }
+                for broadcasting in &cv.notify_all {
+                    let t = net.add_transition();
+                    t.name(net, format!("Condvar_{} notify_all", index))?;
+                    net.add_arc(*broadcasting, t)?;
+                    net.add_arc(t, *broadcasting)?;
+                    Self::wake(net, waiter, t)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn wake(net: &mut Net, waiter: &Waiter, transition: NodeRef) -> Result<()> {
+        net.add_arc(waiter.waiting, transition)?;
+        net.add_arc(transition, waiter.notified)?;
+        net.add_arc(transition, waiter.not_waiting)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ChannelList {
+    list: Vec<Channel>,
+    links: HashMap<Local, ChannelRef>,
+    senders: HashMap<Local, SenderRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct ChannelRef {
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct SenderRef {
+    channel: ChannelRef,
+    index: usize,
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum Capacity {
+    Unbounded,
+    /// A free and a full place per slot. The channel is empty if every slot is free.
+    Bounded(Vec<(NodeRef, NodeRef)>),
+    Rendezvous {
+        taken: NodeRef,       // marked when the receiver took the message
+        receiving: NodeRef,   // marked while a receiver waits in `recv`
+        handed_over: NodeRef, // a message of `try_send` to a waiting receiver
+    },
+}
+
+/// Every sender creation (`channel`, `sync_channel` and clones) gets its own alive and
+/// dropped place. A receiver is disconnected if all of them are dropped,
+/// which is tested by the transitions in `disconnected` after the translation.
+#[derive(Debug)]
+pub struct Channel {
+    messages: NodeRef,
+    capacity: Capacity,
+    receiver_alive: NodeRef,
+    receiver_dropped: NodeRef,
+    senders: Vec<(NodeRef, NodeRef)>, // (alive, dropped)
+    disconnected: Vec<NodeRef>,
+}
+
+impl ChannelRef {
+    pub fn messages(&self, list: &ChannelList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .messages
+    }
+    pub fn capacity(&self, list: &ChannelList) -> Capacity {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .capacity
+            .clone()
+    }
+    pub fn receiver_alive(&self, list: &ChannelList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .receiver_alive
+    }
+    pub fn receiver_dropped(&self, list: &ChannelList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .receiver_dropped
+    }
+}
+
+impl SenderRef {
+    pub fn alive(&self, list: &ChannelList) -> NodeRef {
+        Self::sender(self, list).0
+    }
+    pub fn dropped(&self, list: &ChannelList) -> NodeRef {
+        Self::sender(self, list).1
+    }
+    fn sender(&self, list: &ChannelList) -> (NodeRef, NodeRef) {
+        *list
+            .list
+            .get(self.channel.index)
+            .expect("channel not found")
+            .senders
+            .get(self.index)
+            .expect("sender not found")
+    }
+}
+
+impl ChannelList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+            senders: HashMap::new(),
+        }
+    }
+
+    /// A channel with a bound of `None` is unbounded.
+    pub fn add(&mut self, net: &mut Net, bound: Option<usize>) -> Result<ChannelRef> {
+        let index = self.list.len();
+        let messages = net.add_place();
+        messages.name(net, format!("Channel_{} messages", index))?;
+        let capacity = match bound {
+            None => Capacity::Unbounded,
+            Some(0) => {
+                let taken = net.add_place();
+                taken.name(net, format!("Channel_{} taken", index))?;
+                let receiving = net.add_place();
+                receiving.name(net, format!("Channel_{} receiving", index))?;
+                let handed_over = net.add_place();
+                handed_over.name(net, format!("Channel_{} handed over", index))?;
+                Capacity::Rendezvous {
+                    taken,
+                    receiving,
+                    handed_over,
+                }
+            }
+            Some(bound) => {
+                let mut slots = Vec::new();
+                for slot in 0..bound {
+                    let free = net.add_place();
+                    free.name(net, format!("Channel_{} slot {} free", index, slot))?;
+                    PlaceRef::try_from(free)?.marking(net, 1)?;
+                    let full = net.add_place();
+                    full.name(net, format!("Channel_{} slot {} full", index, slot))?;
+                    slots.push((free, full));
+                }
+                Capacity::Bounded(slots)
+            }
+        };
+        let receiver_alive = net.add_place();
+        receiver_alive.name(net, format!("Channel_{} receiver alive", index))?;
+        let receiver_dropped = net.add_place();
+        receiver_dropped.name(net, format!("Channel_{} receiver dropped", index))?;
+        self.list.push(Channel {
+            messages,
+            capacity,
+            receiver_alive,
+            receiver_dropped,
+            senders: Vec::new(),
+            disconnected: Vec::new(),
+        });
+        Ok(ChannelRef { index })
+    }
+
+    pub fn add_sender(&mut self, net: &mut Net, channel: ChannelRef) -> Result<SenderRef> {
+        let chan = self.list.get_mut(channel.index).expect("channel not found");
+        let index = chan.senders.len();
+        let alive = net.add_place();
+        alive.name(
+            net,
+            format!("Channel_{} sender_{} alive", channel.index, index),
+        )?;
+        let dropped = net.add_place();
+        dropped.name(
+            net,
+            format!("Channel_{} sender_{} dropped", channel.index, index),
+        )?;
+        chan.senders.push((alive, dropped));
+        Ok(SenderRef { channel, index })
+    }
+
+    /// The transition can only fire if all senders are dropped.
+    pub fn add_disconnected(&mut self, channel: ChannelRef, transition: NodeRef) {
+        self.list
+            .get_mut(channel.index)
+            .expect("channel not found")
+            .disconnected
+            .push(transition);
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&ChannelRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, channel: ChannelRef) {
+        match self.links.insert(local, channel) {
+            None => {}
+            Some(old_channel) => {
+                if old_channel != channel {
+                    warn!("Local '{:?}' was already linked to channel '{:?}'. The old value will be overridden with channel '{:?}'", local, old_channel, channel)
+                }
+            }
+        };
+    }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+        self.senders.remove(&local);
+    }
+
+    pub fn get_sender(&self, local: Local) -> Option<&SenderRef> {
+        self.senders.get(&local)
+    }
+
+    pub fn link_sender(&mut self, local: Local, sender: SenderRef) {
+        self.senders.insert(local, sender);
+    }
+
+    /// Moves the token of the dropped sender or receiver from alive to dropped.
+    /// A bypass allows to drop it again (e.g. if multiple locals were linked to it).
+    pub fn release(
+        &self,
+        net: &mut Net,
+        dropped: Local,
+        is_sender: bool,
+        source: NodeRef,
+        transition: NodeRef,
+        target: NodeRef,
+    ) -> Result<()> {
+        let (alive, dead) = if is_sender {
+            match self.senders.get(&dropped) {
+                Some(sender) => (sender.alive(self), sender.dropped(self)),
+                None => return Ok(()),
+            }
+        } else {
+            match self.links.get(&dropped) {
+                Some(channel) => (channel.receiver_alive(self), channel.receiver_dropped(self)),
+                None => return Ok(()),
+            }
+        };
+        net.add_arc(alive, transition)?;
+        net.add_arc(transition, dead)?;
+
+        let bypass = net.add_transition();
+        bypass.name(net, "drop dropped channel end".into())?;
+        net.add_arc(source, bypass)?;
+        net.add_arc(bypass, target)?;
+        net.add_arc(dead, bypass)?;
+        net.add_arc(bypass, dead)?;
+        Ok(())
+    }
+
+    /// Connects the disconnection tests with the senders.
+    pub fn finalize(&self, net: &mut Net) -> Result<()> {
+        for channel in &self.list {
+            for (_, dropped) in &channel.senders {
+                for transition in &channel.disconnected {
+                    net.add_arc(*dropped, *transition)?;
+                    net.add_arc(*transition, *dropped)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct BarrierList {
+    list: Vec<Barrier>,
+    links: HashMap<Local, BarrierRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct BarrierRef {
+    index: usize,
+}
+
+/// A barrier for n threads counts the arrived threads with the places
+/// `arrived[0]` to `arrived[n-1]` and the threads that still have to leave with
+/// `leaving[0]` (one thread) to `leaving[n-1]` (n threads).
+/// Exactly one of these places is marked, so the barrier opens only if
+/// n threads arrived and closes again after all of them left.
+/// A barrier with an unknown number of threads has no places to count with.
+#[derive(Debug)]
+pub struct Barrier {
+    uninitialized: NodeRef,
+    arrived: Vec<NodeRef>,
+    leaving: Vec<NodeRef>,
+}
+
+impl BarrierRef {
+    pub fn uninitialized(&self, list: &BarrierList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("barrier not found")
+            .uninitialized
+    }
+    pub fn arrived(&self, list: &BarrierList, count: usize) -> NodeRef {
+        *list
+            .list
+            .get(self.index)
+            .expect("barrier not found")
+            .arrived
+            .get(count)
+            .expect("barrier count out of range")
+    }
+    pub fn leaving(&self, list: &BarrierList, count: usize) -> NodeRef {
+        *list
+            .list
+            .get(self.index)
+            .expect("barrier not found")
+            .leaving
+            .get(count - 1)
+            .expect("barrier count out of range")
+    }
+    /// `None` if the number of threads is not known.
+    pub fn participants(&self, list: &BarrierList) -> Option<usize> {
+        let participants = list
+            .list
+            .get(self.index)
+            .expect("barrier not found")
+            .arrived
+            .len();
+        if participants > 0 {
+            Some(participants)
+        } else {
+            None
+        }
+    }
+}
+
+impl BarrierList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, net: &mut Net, participants: Option<usize>) -> Result<BarrierRef> {
+        let index = self.list.len();
+        let uninitialized = net.add_place();
+        uninitialized.name(net, format!("Barrier_{} uninitialized", index))?;
+        PlaceRef::try_from(uninitialized)?.marking(net, 1)?;
+        let mut arrived = Vec::new();
+        let mut leaving = Vec::new();
+        for count in 0..participants.unwrap_or(0) {
+            let place = net.add_place();
+            place.name(net, format!("Barrier_{} arrived {}", index, count))?;
+            arrived.push(place);
+            let place = net.add_place();
+            place.name(net, format!("Barrier_{} leaving {}", index, count + 1))?;
+            leaving.push(place);
+        }
+        self.list.push(Barrier {
+            uninitialized,
+            arrived,
+            leaving,
+        });
+        Ok(BarrierRef { index })
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&BarrierRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, barrier: BarrierRef) {
+        match self.links.insert(local, barrier) {
+            None => {}
+            Some(old_barrier) => {
+                if old_barrier != barrier {
+                    warn!("Local '{:?}' was already linked to barrier '{:?}'. The old value will be overridden with barrier '{:?}'", local, old_barrier, barrier)
+                }
+            }
+        };
+    }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+    }
+}
+
+#[derive(Debug)]
+pub struct OnceList {
+    list: Vec<Once>,
+    links: HashMap<Local, OnceRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct OnceRef {
+    index: usize,
+}
+
+/// `incomplete` is marked until the first caller starts the initialization.
+/// Every other caller waits until `complete` is marked.
+#[derive(Debug)]
+pub struct Once {
+    incomplete: NodeRef,
+    running: NodeRef,
+    complete: NodeRef,
+}
+
+impl OnceRef {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn incomplete(&self, list: &OnceList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("once not found")
+            .incomplete
+    }
+    pub fn running(&self, list: &OnceList) -> NodeRef {
+        list.list.get(self.index).expect("once not found").running
+    }
+    pub fn complete(&self, list: &OnceList) -> NodeRef {
+        list.list.get(self.index).expect("once not found").complete
+    }
+}
+
+impl OnceList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, net: &mut Net) -> Result<OnceRef> {
+        let index = self.list.len();
+        let incomplete = net.add_place();
+        incomplete.name(net, format!("Once_{} incomplete", index))?;
+        PlaceRef::try_from(incomplete)?.marking(net, 1)?;
+        let running = net.add_place();
+        running.name(net, format!("Once_{} running", index))?;
+        let complete = net.add_place();
+        complete.name(net, format!("Once_{} complete", index))?;
+        self.list.push(Once {
+            incomplete,
+            running,
+            complete,
+        });
+        Ok(OnceRef { index })
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&OnceRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, once: OnceRef) {
+        match self.links.insert(local, once) {
             None => {}
-            Some(old_mutex) => {
-                if old_mutex != mutex {
-                    warn!("Local '{:?}' was already linked to mutex '{:?}'. The old value will be overridden with mutex '{:?}'", local, old_mutex, mutex)
+            Some(old_once) => {
+                if old_once != once {
+                    warn!("Local '{:?}' was already linked to once '{:?}'. The old value will be overridden with once '{:?}'", local, old_once, once)
                 }
             }
         };
     }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+    }
+}
+
+#[derive(Debug)]
+pub struct AtomicList {
+    list: Vec<Atomic>,
+    links: HashMap<Local, AtomicRef>,
+    outcomes: HashMap<Local, Outcome>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct AtomicRef {
+    index: usize,
+}
+
+/// Booleans are modeled with one place per value so that flags and spinlocks keep
+/// their state. All other atomics share one place that is read and written by
+/// every access, which orders the accesses but ignores the value.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum Atomic {
+    Bool {
+        false_place: NodeRef,
+        true_place: NodeRef,
+    },
+    Value(NodeRef),
+}
+
+/// The outcome of a compare exchange is remembered for the local that holds its result.
+/// The targets of a switch on the result read the outcome they stand for,
+/// so a failed exchange cannot continue in the branch of a successful one.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Outcome {
+    undecided: NodeRef,
+    success: NodeRef,
+    failure: NodeRef,
+    success_value: u128, // the switch value of a successful exchange
+}
+
+impl Outcome {
+    pub fn new(net: &mut Net, name: &str, success_value: u128) -> Result<Self> {
+        let undecided = net.add_place();
+        undecided.name(net, format!("{} undecided", name))?;
+        PlaceRef::try_from(undecided)?.marking(net, 1)?;
+        let success = net.add_place();
+        success.name(net, format!("{} succeeded", name))?;
+        let failure = net.add_place();
+        failure.name(net, format!("{} failed", name))?;
+        Ok(Self {
+            undecided,
+            success,
+            failure,
+            success_value,
+        })
+    }
+
+    pub fn place(&self, success: bool) -> NodeRef {
+        if success {
+            self.success
+        } else {
+            self.failure
+        }
+    }
+
+    /// Continues from `from` to `to` and replaces the outcome of the previous exchange.
+    pub fn decide(&self, net: &mut Net, from: NodeRef, to: NodeRef, success: bool) -> Result<()> {
+        for previous in &[self.undecided, self.success, self.failure] {
+            let t = net.add_transition();
+            t.name(
+                net,
+                format!("decide {}", if success { "success" } else { "failure" }),
+            )?;
+            net.add_arc(from, t)?;
+            net.add_arc(*previous, t)?;
+            net.add_arc(t, to)?;
+            net.add_arc(t, self.place(success))?;
+        }
+        Ok(())
+    }
+
+    /// Lets the transition of a switch target read the outcome of its value,
+    /// `None` is the otherwise target.
+    pub fn read(
+        &self,
+        net: &mut Net,
+        transition: NodeRef,
+        value: Option<u128>,
+        values: &[u128],
+    ) -> Result<()> {
+        let success = match value {
+            Some(value) => value == self.success_value,
+            None => !values.contains(&self.success_value),
+        };
+        net.add_arc(self.place(success), transition)?;
+        net.add_arc(transition, self.place(success))?;
+        Ok(())
+    }
+}
+
+impl AtomicRef {
+    pub fn get(&self, list: &AtomicList) -> Atomic {
+        *list.list.get(self.index).expect("atomic not found")
+    }
+}
+
+impl Atomic {
+    /// The place that is marked if the atomic bool has the given value.
+    pub fn bool_place(&self, value: bool) -> NodeRef {
+        match self {
+            Atomic::Bool {
+                false_place,
+                true_place,
+            } => {
+                if value {
+                    *true_place
+                } else {
+                    *false_place
+                }
+            }
+            Atomic::Value(_) => panic!("atomic is no bool"),
+        }
+    }
+}
+
+impl AtomicList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+            outcomes: HashMap::new(),
+        }
+    }
+
+    /// The places of a new atomic are unmarked, the constructor initializes them.
+    pub fn add(&mut self, net: &mut Net, is_bool: bool) -> Result<AtomicRef> {
+        let index = self.list.len();
+        let atomic = if is_bool {
+            let false_place = net.add_place();
+            false_place.name(net, format!("Atomic_{} false", index))?;
+            let true_place = net.add_place();
+            true_place.name(net, format!("Atomic_{} true", index))?;
+            Atomic::Bool {
+                false_place,
+                true_place,
+            }
+        } else {
+            let value = net.add_place();
+            value.name(net, format!("Atomic_{} value", index))?;
+            Atomic::Value(value)
+        };
+        self.list.push(atomic);
+        Ok(AtomicRef { index })
+    }
+
+    /// An atomic with an initial value of zero (or false),
+    /// e.g. a static that was not initialized by a call to `new`.
+    pub fn add_initialized(&mut self, net: &mut Net, is_bool: bool) -> Result<AtomicRef> {
+        let atomic = self.add(net, is_bool)?;
+        let initial = match atomic.get(self) {
+            Atomic::Bool { false_place, .. } => false_place,
+            Atomic::Value(value) => value,
+        };
+        PlaceRef::try_from(initial)?.marking(net, 1)?;
+        Ok(atomic)
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&AtomicRef> {
+        self.links.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, atomic: AtomicRef) {
+        match self.links.insert(local, atomic) {
+            None => {}
+            Some(old_atomic) => {
+                if old_atomic != atomic {
+                    warn!("Local '{:?}' was already linked to atomic '{:?}'. The old value will be overridden with atomic '{:?}'", local, old_atomic, atomic)
+                }
+            }
+        };
+    }
+
+    pub fn unlink(&mut self, local: Local) {
+        self.links.remove(&local);
+        self.outcomes.remove(&local);
+    }
+
+    pub fn get_outcome(&self, local: Local) -> Option<&Outcome> {
+        self.outcomes.get(&local)
+    }
+
+    /// A local holds the result of the last exchange that was assigned to it.
+    pub fn link_outcome(&mut self, local: Local, outcome: Outcome) {
+        self.outcomes.insert(local, outcome);
+    }
 }
@@ -0,0 +1,207 @@
+//! Links the nodes of the net back to the rust source.
+//!
+//! The translator records where the nodes of statements, terminators and locals came from.
+//! Transitions that are not recorded themselves (e.g. the lock operations of a call)
+//! take the location of the control flow place they consume from.
+//! The locations are emitted as `toolspecific` elements in pnml, as tooltips in dot
+//! and as a json map from node ids to locations.
+
+use crate::petri_net::net::Net;
+use petri_to_star::NodeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Node ids as used in the output formats (e.g. `p_3` or `t_5`) mapped to their locations.
+pub type Locations = BTreeMap<String, SourceLocation>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanKind {
+    Block,
+    Statement,
+    Terminator,
+    Local,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub kind: SpanKind,
+    pub function: String,
+    pub block: Option<usize>,
+    pub statement: Option<usize>,
+    /// the mir of the statement or terminator, or the name of the local
+    pub description: String,
+    pub span: String,
+    /// the spans of the calls that led into the function, innermost first
+    pub callers: Vec<String>,
+    /// `None` is the main thread
+    pub thread: Option<String>,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function)?;
+        if let Some(block) = self.block {
+            write!(f, " bb {}", block)?;
+        }
+        if let Some(statement) = self.statement {
+            write!(f, "[{}]", statement)?;
+        }
+        write!(f, ": {} at {}", self.description, self.span)
+    }
+}
+
+/// The side table of the translator.
+#[derive(Debug)]
+pub struct SpanTable {
+    nodes: HashMap<NodeRef, SourceLocation>,
+}
+
+impl SpanTable {
+    pub fn new() -> Self {
+        SpanTable {
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// The first location of a node is kept,
+    /// e.g. the start place of a called function belongs to the call.
+    pub fn insert(&mut self, node: NodeRef, location: SourceLocation) {
+        self.nodes.entry(node).or_insert(location);
+    }
+
+    /// Resolves the locations of all nodes of the net by their ids.
+    pub fn resolve(&self, net: &Net) -> Locations {
+        let mut locations: Locations = self
+            .nodes
+            .iter()
+            .map(|(node, location)| (net.id(*node).to_string(), location.clone()))
+            .collect();
+        for transition in net.transitions() {
+            if self.nodes.contains_key(transition) {
+                continue;
+            }
+            // locals are read by every statement that uses them,
+            // so the control flow places are more precise
+            let location = net
+                .preset(*transition)
+                .iter()
+                .filter_map(|place| self.nodes.get(place))
+                .min_by_key(|location| location.kind == SpanKind::Local);
+            if let Some(location) = location {
+                locations.insert(net.id(*transition).to_string(), location.clone());
+            }
+        }
+        locations
+    }
+}
+
+/// Adds a `toolspecific` element to every place and transition with a location.
+pub fn annotate_pnml(pnml: &str, locations: &Locations) -> String {
+    let mut annotated = String::with_capacity(pnml.len());
+    let mut rest = pnml;
+    while let Some(start) = next_node_tag(rest) {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        let tag = &rest[start..end];
+        annotated.push_str(&rest[..end]);
+        let location = attribute(tag, "id").and_then(|id| locations.get(id));
+        if let (Some(location), false) = (location, tag.ends_with("/>")) {
+            annotated.push_str(&toolspecific(location));
+        }
+        rest = &rest[end..];
+    }
+    annotated.push_str(rest);
+    annotated
+}
+
+/// Adds a tooltip to every node statement of a node with a location.
+pub fn annotate_dot(dot: &str, locations: &Locations) -> String {
+    let mut annotated = String::with_capacity(dot.len());
+    for line in dot.lines() {
+        let trimmed = line.trim_start();
+        let id: String = trimmed
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        let is_node = !line.contains("->") && trimmed[id.len()..].trim_start().starts_with('[');
+        match locations.get(&id) {
+            Some(location) if is_node => {
+                let bracket = line.find('[').expect("no attribute list found");
+                annotated.push_str(&line[..=bracket]);
+                annotated.push_str(&format!("tooltip=\"{}\", ", tooltip(location)));
+                annotated.push_str(&line[bracket + 1..]);
+            }
+            _ => annotated.push_str(line),
+        }
+        annotated.push('\n');
+    }
+    annotated
+}
+
+fn next_node_tag(pnml: &str) -> Option<usize> {
+    let place = pnml.find("<place ");
+    let transition = pnml.find("<transition ");
+    match (place, transition) {
+        (Some(place), Some(transition)) => Some(place.min(transition)),
+        (place, transition) => place.or(transition),
+    }
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+fn toolspecific(location: &SourceLocation) -> String {
+    let mut element = format!(
+        "<toolspecific tool=\"granite\" version=\"0.1\"><location kind=\"{}\" function=\"{}\"",
+        escape_xml(&format!("{:?}", location.kind).to_lowercase()),
+        escape_xml(&location.function)
+    );
+    if let Some(block) = location.block {
+        element.push_str(&format!(" block=\"{}\"", block));
+    }
+    if let Some(statement) = location.statement {
+        element.push_str(&format!(" statement=\"{}\"", statement));
+    }
+    if let Some(thread) = &location.thread {
+        element.push_str(&format!(" thread=\"{}\"", escape_xml(thread)));
+    }
+    element.push_str(&format!(
+        " span=\"{}\" description=\"{}\">",
+        escape_xml(&location.span),
+        escape_xml(&location.description)
+    ));
+    for caller in &location.callers {
+        element.push_str(&format!("<caller span=\"{}\"/>", escape_xml(caller)));
+    }
+    element.push_str("</location></toolspecific>");
+    element
+}
+
+fn tooltip(location: &SourceLocation) -> String {
+    let mut lines = vec![location.to_string()];
+    lines.extend(
+        location
+            .callers
+            .iter()
+            .map(|caller| format!("called at {}", caller)),
+    );
+    lines
+        .iter()
+        .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect::<Vec<_>>()
+        .join("\\n")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
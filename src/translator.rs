@@ -1,13 +1,24 @@
-use crate::petri_net::function::{Data, Function, Local};
-use crate::petri_net::unique_functions::MutexList;
-use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use crate::entry::has_type_params;
+use crate::lock_api::{LockApi, LockOperation};
+use crate::petri_net::function::{
+    field_path, field_path_name, Data, FieldMemory, FieldPath, FieldStep, Function, Local,
+};
+use crate::petri_net::net::Net;
+use crate::petri_net::unique_functions::{
+    Atomic, AtomicList, BarrierList, Capacity, ChannelList, CondvarList, MutexList, MutexRef,
+    OnceList, Outcome, RwLockList, ThreadList, ThreadRef,
+};
+use crate::spans::{SourceLocation, SpanKind, SpanTable};
+use petri_to_star::{NodeRef, PlaceRef, Result};
 use rustc::mir::visit::Visitor;
 use rustc::mir::visit::*;
 use rustc::mir::{self, *};
-use rustc::ty::{self, Ty, TyCtxt};
+use rustc::ty::adjustment::PointerCast;
+use rustc::ty::subst::{InternalSubsts, Subst, SubstsRef};
+use rustc::ty::{self, Instance, InstanceDef, Ty, TyCtxt};
 use rustc_hir::def_id::DefId;
 use rustc_mir::util::write_mir_pretty;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
 struct CallStack<T> {
@@ -47,17 +58,79 @@ impl<T> CallStack<T> {
     pub fn is_empty(&self) -> bool {
         self.stack.is_empty()
     }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.stack.iter()
+    }
+}
+
+/// Options that change the semantics of the generated net.
+#[derive(Debug, Default, Clone)]
+pub struct TranslatorConfig {
+    /// waiting condvars can wake up without being notified
+    pub spurious_wakeups: bool,
+    /// how often a function may call itself (directly or indirectly) before
+    /// the recursion is cut off
+    pub recursion_depth: usize,
+    /// translate every function only once and connect all call sites to it
+    pub function_summaries: bool,
+    /// types and functions that are translated like `std::sync::Mutex`
+    pub lock_api: LockApi,
+    /// how many nested field projections get their own places,
+    /// deeper parts of a local are represented by their parent
+    pub field_depth: usize,
+    /// track which locals own a value, values are moved by `Operand::Move`
+    /// and dropping a moved value has no effect
+    pub ownership: bool,
+    /// how many mutexes an allocation site stands for if it is reached
+    /// from a loop, calls choose one of them nondeterministically
+    pub loop_instances: usize,
+}
+
+/// The shared subnet of a function that is translated only once.
+/// Every call site marks its own place while the function is active,
+/// so that the return goes back to the right caller.
+/// Call sites share a summary if their arguments are linked to the same primitives,
+/// otherwise the function gets another summary for their links.
+#[derive(Debug, Clone)]
+struct Summary {
+    entry: NodeRef,
+    exit: NodeRef,
+    data_return: Local,
+    args: Vec<Local>,
+    call_sites: usize,
 }
 
 pub struct Translator<'tcx> {
     tcx: TyCtxt<'tcx>,
+    config: TranslatorConfig,
     call_stack: CallStack<Function<'tcx>>,
     visited: HashSet<DefId>,
-    net: PetriNet,
+    substs_stack: CallStack<SubstsRef<'tcx>>, // substitutions of the functions on the call stack
+    summaries: HashMap<(DefId, SubstsRef<'tcx>, Vec<String>), Summary>,
+    static_items: HashMap<DefId, Local>,
+    fields: FieldMemory,
+    pointees: HashMap<Local, Local>, // the locals (or fields) references point to
+    fn_pointers: HashMap<Local, Vec<(DefId, SubstsRef<'tcx>)>>, // points-to sets of function pointers
+    address_taken: Vec<(DefId, SubstsRef<'tcx>)>, // all functions that were cast to pointers
+    net: Net,
     mutex_list: MutexList,
+    threads: ThreadList,
+    current_thread: Option<ThreadRef>, // None is the main thread
+    rw_locks: RwLockList,
+    condvars: CondvarList,
+    channels: ChannelList,
+    barriers: BarrierList,
+    onces: OnceList,
+    atomics: AtomicList,
     unwind_abort_place: NodeRef,
     program_end_place: Option<NodeRef>,
-    mir_dump: Option<std::fs::File>,
+    recursion_bound_place: Option<NodeRef>,
+    mir_dump: Option<Box<dyn std::io::Write>>,
+    mir_dump_error: Option<std::io::Error>,
+    spans: SpanTable,
+    call_spans: Vec<String>, // the calls that led to the function on top of the call stack
+    loop_calls: Vec<bool>,   // which of these calls are part of a loop
 }
 
 macro_rules! net {
@@ -73,23 +146,50 @@ macro_rules! function {
 }
 
 impl<'tcx> Translator<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>, mir_dump: Option<std::fs::File>) -> Result<Self> {
-        let mut net = PetriNet::new();
+    pub fn new(
+        tcx: TyCtxt<'tcx>,
+        mir_dump: Option<Box<dyn std::io::Write>>,
+        config: TranslatorConfig,
+    ) -> Result<Self> {
+        let mut net = Net::new();
         let unwind_abort_place = net.add_place();
         unwind_abort_place.name(&mut net, "unwind_abort".into())?;
         Ok(Translator {
             tcx,
+            config,
             call_stack: CallStack::new(),
             visited: HashSet::new(),
+            substs_stack: CallStack::new(),
+            summaries: HashMap::new(),
+            static_items: HashMap::new(),
+            fields: FieldMemory::new(),
+            pointees: HashMap::new(),
+            fn_pointers: HashMap::new(),
+            address_taken: Vec::new(),
             net,
             mutex_list: MutexList::new(),
+            threads: ThreadList::new(),
+            current_thread: None,
+            rw_locks: RwLockList::new(),
+            condvars: CondvarList::new(),
+            channels: ChannelList::new(),
+            barriers: BarrierList::new(),
+            onces: OnceList::new(),
+            atomics: AtomicList::new(),
             unwind_abort_place,
             program_end_place: None,
+            recursion_bound_place: None,
             mir_dump,
+            mir_dump_error: None,
+            spans: SpanTable::new(),
+            call_spans: Vec::new(),
+            loop_calls: Vec::new(),
         })
     }
 
-    pub fn petrify(&mut self, main_fn: DefId) -> Result<&PetriNet> {
+    /// Translates the entry functions into one net.
+    /// Multiple entries are started nondeterministically, only one of them runs.
+    pub fn petrify(&mut self, entries: &[DefId]) -> Result<()> {
         let start_place = {
             let net = net!(self);
             let place = net.add_place();
@@ -102,32 +202,85 @@ impl<'tcx> Translator<'tcx> {
             place.name(net, "program end".into())?;
             Some(place)
         };
-        let data_return = Local::new(net!(self), "main_return")?;
-        self.translate(
-            main_fn,
-            Vec::new(), //TODO: Arguments would be important for HiLvl Nets
-            data_return,
-            start_place,
-            self.program_end_place
-                .expect("no program end place defined"),
-        )?;
-        Ok(&self.net)
+        self.recursion_bound_place = {
+            let net = net!(self);
+            let place = net.add_place();
+            place.name(net, "recursion bound exceeded".into())?;
+            Some(place)
+        };
+        for entry in entries {
+            let fn_name = self.tcx.def_path_str(*entry);
+            let entry_start = if entries.len() == 1 {
+                start_place
+            } else {
+                let net = net!(self);
+                let place = net.add_place();
+                let t = net.add_transition();
+                t.name(net, format!("start {}", fn_name))?;
+                net.add_arc(start_place, t)?;
+                net.add_arc(t, place)?;
+                place
+            };
+            let data_return = self.return_local(&format!("{}_return", fn_name))?;
+            // the values of the arguments are unknown but always available
+            let mut args = Vec::new();
+            for index in 1..=self.tcx.optimized_mir(*entry).arg_count {
+                let name = format!("{} argument {}", fn_name, index);
+                let mut arg = Local::new_untracked(net!(self), &name)?;
+                if self.config.ownership {
+                    arg.add_value(net!(self), &name, true)?;
+                }
+                args.push(arg);
+            }
+            self.translate(
+                *entry,
+                InternalSubsts::identity_for_item(self.tcx, *entry),
+                args,
+                Vec::new(),
+                data_return,
+                entry_start,
+                self.program_end_place
+                    .expect("no program end place defined"),
+            )?;
+        }
+        self.rw_locks.finalize(net!(self))?;
+        self.condvars.finalize(net!(self))?;
+        self.channels.finalize(net!(self))?;
+        Ok(())
+    }
+
+    pub fn net(&self) -> &Net {
+        &self.net
+    }
+
+    /// The source locations of the nodes of the net.
+    pub fn spans(&self) -> &SpanTable {
+        &self.spans
+    }
+
+    /// The first error that stopped the mir dump, the translation goes on without it.
+    pub fn take_mir_dump_error(&mut self) -> Option<std::io::Error> {
+        self.mir_dump_error.take()
     }
 
     fn translate<'a>(
         &mut self,
         function: DefId,
+        substs: SubstsRef<'tcx>,
         args: Vec<Local>,
+        const_args: Vec<Option<u128>>, // values of constant arguments
         data_return: Local,
         start_place: NodeRef,
         return_flow: NodeRef,
     ) -> Result<()> {
         let fn_name = self.tcx.def_path_str(function);
         start_place.name(&mut self.net, fn_name.clone())?;
-        if Self::is_unique(&fn_name) {
+        if self.is_unique(function, &fn_name) {
             self.translate_unique(
                 function,
+                substs,
                 args,
+                const_args,
                 data_return,
                 start_place,
                 return_flow,
@@ -136,6 +289,7 @@ impl<'tcx> Translator<'tcx> {
         } else {
             self.translate_default(
                 function,
+                substs,
                 args,
                 data_return,
                 start_place,
@@ -161,11 +315,218 @@ impl<'tcx> Translator<'tcx> {
         }
     }
 
-    pub fn is_unique(name: &str) -> bool {
+    pub fn is_unique(&self, function: DefId, name: &str) -> bool {
+        match name {
+            name if self.config.lock_api.operation(name).is_some()
+                | name.contains("std::thread::spawn")
+                | name.contains("std::thread::JoinHandle::<T>::join")
+                | name.contains("std::sync::RwLock::<T>::new")
+                | name.contains("std::sync::RwLock::<T>::read")
+                | name.contains("std::sync::RwLock::<T>::write")
+                | name.contains("std::sync::RwLock::<T>::try_read")
+                | name.contains("std::sync::RwLock::<T>::try_write")
+                | (name == "std::sync::Condvar::new")
+                | Self::is_condvar_wait(name)
+                | (name == "std::sync::Condvar::notify_one")
+                | (name == "std::sync::Condvar::notify_all")
+                | Self::is_channel_operation(name)
+                | name.contains("std::sync::Barrier::new")
+                | name.contains("std::sync::Barrier::wait")
+                | name.contains("std::sync::Once::new")
+                | (name == "std::sync::Once::call_once")
+                | name.contains("once_cell::sync::OnceCell::<T>::get_or_init")
+                | Self::is_atomic_operation(name)
+                | Self::is_executor(self.tcx, function, name) =>
+            {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Resolves the callee of a call to the functions that can actually be executed,
+    /// e.g. the impl of a trait method.
+    /// The substitutions of the call are expressed in the generics of the caller
+    /// and are substituted with the ones of the caller first.
+    /// Calls on trait objects can execute any impl of the trait.
+    fn resolve(
+        tcx: TyCtxt<'tcx>,
+        caller_substs: Option<SubstsRef<'tcx>>,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+    ) -> Vec<(DefId, SubstsRef<'tcx>)> {
+        let param_env = ty::ParamEnv::reveal_all();
+        let substs = match caller_substs {
+            Some(caller_substs) => {
+                tcx.subst_and_normalize_erasing_regions(caller_substs, param_env, &substs)
+            }
+            None => substs,
+        };
+        match Instance::resolve(tcx, param_env, function, substs) {
+            Some(Instance {
+                def: InstanceDef::Item(def_id),
+                substs,
+            }) => {
+                if def_id != function {
+                    debug!(
+                        "resolved {} to {}",
+                        tcx.def_path_str(function),
+                        tcx.def_path_str(def_id)
+                    );
+                }
+                vec![(def_id, substs)]
+            }
+            Some(Instance {
+                def: InstanceDef::Virtual(method, _),
+                ..
+            }) => {
+                let callees = Self::virtual_callees(tcx, method);
+                if callees.is_empty() {
+                    warn!("no impl found for {}", tcx.def_path_str(method));
+                    vec![(function, substs)]
+                } else {
+                    callees
+                }
+            }
+            // calls of `FnOnce::call_once` on closures that implement `Fn` or `FnMut`
+            Some(Instance {
+                def: InstanceDef::ClosureOnceShim { .. },
+                substs,
+            }) => match Self::closure_in_substs(substs) {
+                Some(closure) => vec![closure],
+                None => vec![(function, substs)],
+            },
+            // shims have no mir of their own and are emulated
+            Some(instance) => {
+                debug!("cannot resolve {:?} to an item", instance);
+                vec![(function, substs)]
+            }
+            None => {
+                debug!("cannot resolve {}", tcx.def_path_str(function));
+                vec![(function, substs)]
+            }
+        }
+    }
+
+    /// The implementations of a trait method in the local crate.
+    /// Generic impls are skipped because their substitutions are unknown,
+    /// so the callees of the trait object are incomplete if any impl was skipped.
+    fn virtual_callees(tcx: TyCtxt<'tcx>, method: DefId) -> Vec<(DefId, SubstsRef<'tcx>)> {
+        let method_name = tcx.associated_item(method).ident.name;
+        let trait_id = match tcx.trait_of_item(method) {
+            Some(trait_id) => trait_id,
+            None => return Vec::new(),
+        };
+        let mut callees = Vec::new();
+        let mut skipped = 0;
+        tcx.for_each_impl(trait_id, |impl_id| {
+            if !impl_id.is_local() {
+                skipped += 1;
+                return;
+            }
+            if has_type_params(tcx, impl_id) {
+                warn!("skipping generic impl {}", tcx.def_path_str(impl_id));
+                skipped += 1;
+                return;
+            }
+            let implementation = tcx
+                .associated_items(impl_id)
+                .find(|item| item.ident.name == method_name);
+            match implementation {
+                Some(item) => callees.push((
+                    item.def_id,
+                    InternalSubsts::identity_for_item(tcx, item.def_id),
+                )),
+                // the default implementation of the trait with this impl as Self
+                None => {
+                    let trait_ref = tcx.impl_trait_ref(impl_id).expect("no trait impl");
+                    callees.push((method, trait_ref.substs))
+                }
+            }
+        });
+        if skipped > 0 {
+            warn!(
+                "incomplete callees of {}: {} impls of other crates or with generics are missing",
+                tcx.def_path_str(method),
+                skipped
+            );
+        }
+        callees
+    }
+
+    /// The functions a function item or closure type refers to
+    /// when it is cast to a function pointer.
+    fn pointer_targets(
+        tcx: TyCtxt<'tcx>,
+        caller_substs: Option<SubstsRef<'tcx>>,
+        ty: Ty<'tcx>,
+    ) -> Vec<(DefId, SubstsRef<'tcx>)> {
+        match ty.kind {
+            ty::FnDef(def_id, substs) => Self::resolve(tcx, caller_substs, def_id, substs),
+            ty::Closure(def_id, substs) => {
+                let substs = match caller_substs {
+                    Some(caller_substs) => tcx.subst_and_normalize_erasing_regions(
+                        caller_substs,
+                        ty::ParamEnv::reveal_all(),
+                        &substs,
+                    ),
+                    None => substs,
+                };
+                vec![(def_id, substs)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The functions a called function pointer can point to.
+    /// If the pointer was not tracked every address taken function with the
+    /// same number of arguments is a possible target.
+    fn fn_pointer_callees(
+        &self,
+        pointer: Option<Local>,
+        signature: ty::PolyFnSig<'tcx>,
+    ) -> Vec<(DefId, SubstsRef<'tcx>)> {
+        if let Some(targets) = pointer.and_then(|pointer| self.fn_pointers.get(&pointer)) {
+            return targets.clone();
+        }
+        let arg_count = signature.inputs().skip_binder().len();
+        self.address_taken
+            .iter()
+            .filter(|(def_id, _)| {
+                let count = if self.tcx.is_closure(*def_id) {
+                    // without the closure environment
+                    self.tcx.optimized_mir(*def_id).arg_count - 1
+                } else {
+                    self.tcx.fn_sig(*def_id).inputs().skip_binder().len()
+                };
+                count == arg_count
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Finds the closure type in the given substitutions.
+    /// Functions like `std::thread::spawn` are generic over the closure they execute.
+    fn closure_in_substs(substs: SubstsRef<'tcx>) -> Option<(DefId, SubstsRef<'tcx>)> {
+        for ty in substs.types() {
+            if let ty::Closure(def_id, closure_substs) = ty.kind {
+                return Some((def_id, closure_substs));
+            }
+        }
+        None
+    }
+
+    fn is_channel_operation(name: &str) -> bool {
         match name {
-            name if name.contains("std::sync::Mutex::<T>::new")
-                | name.contains("std::sync::Mutex::<T>::lock")
-                | name.contains("std::sync::Mutex::<T>::try_lock") =>
+            name if name.contains("std::sync::mpsc::channel")
+                | name.contains("std::sync::mpsc::sync_channel")
+                | name.contains("std::sync::mpsc::Sender<T> as std::clone::Clone>::clone")
+                | name.contains("std::sync::mpsc::SyncSender<T> as std::clone::Clone>::clone")
+                | name.contains("std::sync::mpsc::Sender::<T>::send")
+                | name.contains("std::sync::mpsc::SyncSender::<T>::send")
+                | name.contains("std::sync::mpsc::SyncSender::<T>::try_send")
+                | name.contains("std::sync::mpsc::Receiver::<T>::recv")
+                | name.contains("std::sync::mpsc::Receiver::<T>::try_recv") =>
             {
                 true
             }
@@ -173,9 +534,566 @@ impl<'tcx> Translator<'tcx> {
         }
     }
 
+    /// The waiting methods of `std::sync::Condvar`, `wait_until` and `wait_timeout_until`
+    /// are the names of the `*_while` methods before their stabilization.
+    fn is_condvar_wait(name: &str) -> bool {
+        match name {
+            "std::sync::Condvar::wait"
+            | "std::sync::Condvar::wait_while"
+            | "std::sync::Condvar::wait_until"
+            | "std::sync::Condvar::wait_timeout"
+            | "std::sync::Condvar::wait_timeout_ms"
+            | "std::sync::Condvar::wait_timeout_while"
+            | "std::sync::Condvar::wait_timeout_until" => true,
+            _ => false,
+        }
+    }
+
+    /// Operations on the atomic types of `std::sync::atomic`.
+    /// Other methods like `get_mut` or `into_inner` are translated as usual.
+    fn is_atomic_operation(name: &str) -> bool {
+        if !name.contains("std::sync::atomic::Atomic") {
+            return false;
+        }
+        match name.rsplit("::").next() {
+            Some("new")
+            | Some("load")
+            | Some("store")
+            | Some("swap")
+            | Some("compare_and_swap")
+            | Some("compare_exchange")
+            | Some("compare_exchange_weak") => true,
+            Some(operation) => operation.starts_with("fetch_"),
+            None => false,
+        }
+    }
+
+    /// Functions that poll a future until it is ready: the executors of the common crates
+    /// and generic functions named `block_on` that take a `Future`,
+    /// e.g. the minimal executors of tests and examples.
+    fn is_executor(tcx: TyCtxt<'_>, function: DefId, name: &str) -> bool {
+        match name {
+            "futures::executor::block_on"
+            | "futures_executor::local_pool::block_on"
+            | "async_std::task::block_on"
+            | "tokio::runtime::Runtime::block_on" => true,
+            name if name.rsplit("::").next() == Some("block_on") => {
+                // a bound like `F: Future` on one of the type parameters
+                let future_trait = tcx.lang_items().future_trait();
+                tcx.predicates_of(function).predicates.iter().any(
+                    |(predicate, _)| match predicate {
+                        ty::Predicate::Trait(bound, ..) => Some(bound.def_id()) == future_trait,
+                        _ => false,
+                    },
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// The value of a constant integer or bool operand.
+    fn const_operand(tcx: TyCtxt<'tcx>, operand: &Operand<'tcx>) -> Option<u128> {
+        match operand {
+            Operand::Constant(constant) => {
+                constant
+                    .literal
+                    .try_eval_bits(tcx, ty::ParamEnv::reveal_all(), constant.literal.ty)
+            }
+            _ => None,
+        }
+    }
+
+    /// Carries the links of primitives that are tracked by identity
+    /// (locks, their guards, threads) from one local and its fields to another.
+    fn propagate_links(&mut self, from: Local, to: Local) {
+        if from == to {
+            return;
+        }
+        // a field that was never linked itself refers to the primitives of its parent,
+        // e.g. the content of an `Option<&Mutex<T>>`
+        self.propagate_local_links(self.linked_ancestor(from), to);
+        let (root, prefix) = self.fields.key_of(from);
+        for (suffix, from_field) in self.fields.descendants(root, &prefix) {
+            // fields without links do not need a counterpart
+            if !self.has_local_links(from_field) {
+                continue;
+            }
+            let to_field = self
+                .field(to, &suffix, "moved")
+                .expect("unable to add field");
+            self.propagate_local_links(from_field, to_field);
+        }
+    }
+
+    /// The local itself or, if it has no links, its closest linked parent.
+    fn linked_ancestor(&self, local: Local) -> Local {
+        let (root, path) = self.fields.key_of(local);
+        if self.has_local_links(local) {
+            return local;
+        }
+        for length in (0..path.len()).rev() {
+            let ancestor = match length {
+                0 => root,
+                _ => match self.fields.get(root, &path[..length]) {
+                    Some(field) => *field,
+                    None => continue,
+                },
+            };
+            if self.has_local_links(ancestor) {
+                return ancestor;
+            }
+        }
+        local
+    }
+
+    fn propagate_local_links(&mut self, from: Local, to: Local) {
+        // statics keep the primitives they were initialized with
+        if self.static_items.values().any(|item| *item == to) && self.has_local_links(to) {
+            return;
+        }
+        if let Some(mutexes) = self.mutex_list.get_linked(from).cloned() {
+            debug!("link '{:?}' to mutexes '{:?}'", to, mutexes);
+            for mutex in mutexes {
+                self.mutex_list.link(to, mutex)
+            }
+        }
+        if let Some(guards) = self.mutex_list.get_guards(from).cloned() {
+            debug!("link '{:?}' to guards '{:?}'", to, guards);
+            for guard in guards {
+                self.mutex_list.link_guard(to, guard)
+            }
+        }
+        if let Some(lock) = self.rw_locks.get_linked(from) {
+            debug!("link '{:?}' to rwlock '{:?}'", to, lock);
+            self.rw_locks.link(to, *lock)
+        }
+        if let Some(guard) = self.rw_locks.get_guard(from) {
+            debug!("link '{:?}' to rw guard '{:?}'", to, guard);
+            self.rw_locks.link_guard(to, *guard)
+        }
+        if let Some(condvar) = self.condvars.get_linked(from) {
+            debug!("link '{:?}' to condvar '{:?}'", to, condvar);
+            self.condvars.link(to, *condvar)
+        }
+        if let Some(channel) = self.channels.get_linked(from) {
+            debug!("link '{:?}' to channel '{:?}'", to, channel);
+            self.channels.link(to, *channel)
+        }
+        if let Some(sender) = self.channels.get_sender(from) {
+            debug!("link '{:?}' to sender '{:?}'", to, sender);
+            self.channels.link_sender(to, *sender)
+        }
+        if let Some(barrier) = self.barriers.get_linked(from) {
+            debug!("link '{:?}' to barrier '{:?}'", to, barrier);
+            self.barriers.link(to, *barrier)
+        }
+        if let Some(once) = self.onces.get_linked(from) {
+            debug!("link '{:?}' to once '{:?}'", to, once);
+            self.onces.link(to, *once)
+        }
+        if let Some(atomic) = self.atomics.get_linked(from) {
+            debug!("link '{:?}' to atomic '{:?}'", to, atomic);
+            self.atomics.link(to, *atomic)
+        }
+        if let Some(pointee) = self.pointees.get(&from) {
+            debug!("link '{:?}' to pointee '{:?}'", to, pointee);
+            self.pointees.insert(to, *pointee);
+        }
+        if let Some(outcome) = self.atomics.get_outcome(from) {
+            debug!("link '{:?}' to outcome '{:?}'", to, outcome);
+            self.atomics.link_outcome(to, *outcome)
+        }
+        if let Some(thread) = self.threads.get_linked(from) {
+            debug!("link '{:?}' to thread '{:?}'", to, thread);
+            self.threads.link(to, *thread)
+        }
+        if let Some(targets) = self.fn_pointers.get(&from).cloned() {
+            debug!(
+                "link '{:?}' to function pointer targets '{:?}'",
+                to, targets
+            );
+            let to_targets = self.fn_pointers.entry(to).or_insert_with(Vec::new);
+            for target in targets {
+                if !to_targets.contains(&target) {
+                    to_targets.push(target)
+                }
+            }
+        }
+    }
+
+    /// Forgets the primitives the local (but not its fields) refers to.
+    fn unlink(&mut self, local: Local) {
+        self.mutex_list.unlink(local);
+        self.rw_locks.unlink(local);
+        self.condvars.unlink(local);
+        self.channels.unlink(local);
+        self.barriers.unlink(local);
+        self.onces.unlink(local);
+        self.atomics.unlink(local);
+        self.threads.unlink(local);
+        self.fn_pointers.remove(&local);
+        self.pointees.remove(&local);
+    }
+
+    /// A local for the value a call returns, the ownership of which may be tracked.
+    fn return_local(&mut self, name: &str) -> Result<Local> {
+        if self.config.ownership {
+            Local::new_owned(net!(self), name)
+        } else {
+            Local::new(net!(self), name)
+        }
+    }
+
+    fn has_local_links(&self, local: Local) -> bool {
+        self.mutex_list.get_linked(local).is_some()
+            || self.mutex_list.get_guards(local).is_some()
+            || self.rw_locks.get_linked(local).is_some()
+            || self.rw_locks.get_guard(local).is_some()
+            || self.condvars.get_linked(local).is_some()
+            || self.channels.get_linked(local).is_some()
+            || self.channels.get_sender(local).is_some()
+            || self.barriers.get_linked(local).is_some()
+            || self.onces.get_linked(local).is_some()
+            || self.atomics.get_linked(local).is_some()
+            || self.threads.get_linked(local).is_some()
+            || self.fn_pointers.contains_key(&local)
+    }
+
+    /// A description of the primitives the local and its fields are linked to.
+    fn link_key(&self, local: Local) -> String {
+        let (root, prefix) = self.fields.key_of(local);
+        let mut parts = vec![(Vec::new(), local)];
+        parts.extend(self.fields.descendants(root, &prefix));
+        parts
+            .into_iter()
+            .map(|(path, local)| {
+                format!(
+                    "{:?}: {:?}",
+                    path,
+                    (
+                        self.mutex_list.get_linked(local),
+                        self.mutex_list.get_guards(local),
+                        self.rw_locks.get_linked(local),
+                        self.rw_locks.get_guard(local),
+                        self.condvars.get_linked(local),
+                        self.channels.get_linked(local),
+                        self.channels.get_sender(local),
+                        self.barriers.get_linked(local),
+                        self.onces.get_linked(local),
+                        self.atomics.get_linked(local),
+                        self.threads.get_linked(local),
+                        self.fn_pointers.get(&local),
+                    )
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     fn translate_default(
         &mut self,
         function: DefId,
+        substs: SubstsRef<'tcx>,
+        args: Vec<Local>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let depth = self
+            .call_stack
+            .iter()
+            .filter(|frame| frame.name == fn_name)
+            .count();
+        if self.config.function_summaries && !self.call_stack.is_empty() {
+            // the links keep the identity of the primitives of the arguments,
+            // a recursive call with new links counts towards the recursion bound
+            let links: Vec<String> = args.iter().map(|arg| self.link_key(*arg)).collect();
+            if depth <= self.config.recursion_depth
+                || self
+                    .summaries
+                    .contains_key(&(function, substs, links.clone()))
+            {
+                return self.translate_summary(
+                    function,
+                    substs,
+                    links,
+                    args,
+                    data_return,
+                    start_place,
+                    return_flow,
+                    fn_name,
+                );
+            }
+        }
+        if depth > self.config.recursion_depth {
+            // cut off the recursion, reaching this point ends the thread
+            warn!("recursion bound exceeded in {}", fn_name);
+            let net = net!(self);
+            let t = net.add_transition();
+            t.name(net, format!("{} recursion bound exceeded", fn_name))?;
+            net.add_arc(start_place, t)?;
+            net.add_arc(
+                t,
+                self.recursion_bound_place
+                    .expect("no recursion bound place defined"),
+            )?;
+            return Ok(());
+        }
+        self.translate_body(
+            function,
+            substs,
+            args,
+            data_return,
+            start_place,
+            return_flow,
+            fn_name,
+        )
+    }
+
+    /// The local of the part of `base` on the given path.
+    /// Paths are relative to the local the base is part of and cut off at the field depth.
+    fn field(&mut self, base: Local, path: &[FieldStep], name: &str) -> Result<Local> {
+        let (root, mut full_path) = self.fields.key_of(base);
+        if full_path.len() >= self.config.field_depth {
+            return Ok(base);
+        }
+        full_path.extend(path);
+        full_path.truncate(self.config.field_depth);
+        if let Some(field) = self.fields.get(root, &full_path) {
+            return Ok(*field);
+        }
+        let fn_name = match self.call_stack.peek() {
+            Some(function) => function.name.clone(),
+            None => String::from("static"),
+        };
+        let field_name = format!("{}_{}{}", fn_name, name, field_path_name(path));
+        // like arguments, the fields may outlive the current function
+        let field = Local::new_untracked(net!(self), &field_name)?;
+        self.fields.insert(root, full_path, field);
+        Ok(field)
+    }
+
+    /// Makes the parts of the places that are accessed in a statement
+    /// or terminator known to the current function.
+    fn add_fields(&mut self, places: Vec<Place<'tcx>>) -> Result<()> {
+        for place in places {
+            let path = field_path(&place);
+            if path.is_empty() {
+                continue;
+            }
+            let (base, name) = match self.place_base(&place) {
+                Some(base) => base,
+                None => continue,
+            };
+            let field = self.place_part(base, &place, &name)?;
+            function!(self).add_field(base, path, field);
+        }
+        Ok(())
+    }
+
+    /// The local of a place (without projections) and a name for its fields,
+    /// promoted statics have no fields.
+    fn place_base(&self, place: &Place<'tcx>) -> Option<(Local, String)> {
+        match &place.base {
+            PlaceBase::Local(local) => Some((
+                *self
+                    .call_stack
+                    .peek()
+                    .expect("empty call stack")
+                    .get_local(local)
+                    .expect("local not found"),
+                local.index().to_string(),
+            )),
+            PlaceBase::Static(statik) => match statik.kind {
+                StaticKind::Static => Some((
+                    *self
+                        .static_items
+                        .get(&statik.def_id)
+                        .expect("static item not found"),
+                    self.tcx.def_path_str(statik.def_id),
+                )),
+                StaticKind::Promoted(_, _) => None,
+            },
+        }
+    }
+
+    /// The part of a local a place selects. A dereference continues in the pointee
+    /// of the reference, so `(*_1).0` is a field of the local `_1` points to
+    /// and shares its links with every other reference to it.
+    fn place_part(&mut self, base: Local, place: &Place<'tcx>, name: &str) -> Result<Local> {
+        let mut local = base;
+        let mut path = Vec::new();
+        for elem in place.projection.iter() {
+            match elem {
+                ProjectionElem::Deref => {
+                    if !path.is_empty() {
+                        local = self.field(local, &path, name)?;
+                        path.clear();
+                    }
+                    if let Some(pointee) = self.pointees.get(&local) {
+                        local = *pointee;
+                    }
+                }
+                ProjectionElem::Field(field, _) => path.push(FieldStep::Field(field.index())),
+                ProjectionElem::Downcast(_, variant) => {
+                    path.push(FieldStep::Variant(variant.index()))
+                }
+                _ => break,
+            }
+        }
+        if path.is_empty() {
+            Ok(local)
+        } else {
+            self.field(local, &path, name)
+        }
+    }
+
+    /// The mutexes a lock may point to.
+    /// A lock without a known allocation site (e.g. created by code that is not translated)
+    /// gets a mutex that is unlocked from the start.
+    fn linked_mutexes(&mut self, lock: Local) -> Result<Vec<MutexRef>> {
+        if let Some(mutexes) = self.mutex_list.get_linked(lock) {
+            return Ok(mutexes.clone());
+        }
+        warn!("no allocation site found for lock '{:?}'", lock);
+        let mutex = self.mutex_list.add_initialized(net!(self))?;
+        self.mutex_list.link(lock, mutex);
+        Ok(vec![mutex])
+    }
+
+    /// The local of a static item that is shared by all functions.
+    /// Statics of synchronization primitives are initialized at program start,
+    /// the initialization code (e.g. of `lazy_static`) cannot relink them.
+    fn static_item(&mut self, def_id: DefId) -> Result<Local> {
+        if let Some(local) = self.static_items.get(&def_id) {
+            return Ok(*local);
+        }
+        let name = format!("static {}", self.tcx.def_path_str(def_id));
+        let local = Local::new_untracked(net!(self), &name)?;
+        self.static_items.insert(def_id, local);
+        for ty in self.tcx.type_of(def_id).walk() {
+            let sort_string = ty.sort_string(self.tcx);
+            if self.config.lock_api.is_lock_type(&sort_string) {
+                let mutex = self.mutex_list.add_initialized(net!(self))?;
+                debug!("link '{:?}' to mutex '{:?}'", local, mutex);
+                self.mutex_list.link(local, mutex);
+                break;
+            }
+            if sort_string.starts_with("struct `std::sync::atomic::Atomic") {
+                let is_bool = sort_string == "struct `std::sync::atomic::AtomicBool`";
+                let atomic = self.atomics.add_initialized(net!(self), is_bool)?;
+                debug!("link '{:?}' to atomic '{:?}'", local, atomic);
+                self.atomics.link(local, atomic);
+                break;
+            }
+            if sort_string == "struct `std::sync::Once`" {
+                let once = self.onces.add(net!(self))?;
+                debug!("link '{:?}' to once '{:?}'", local, once);
+                self.onces.link(local, once);
+                break;
+            }
+        }
+        Ok(local)
+    }
+
+    /// Connects the call site to the shared subnet of the function.
+    /// The subnet is translated on the first call. Recursive calls loop back into it.
+    fn translate_summary(
+        &mut self,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+        links: Vec<String>,
+        args: Vec<Local>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let key = (function, substs, links);
+        let summary = match self.summaries.get(&key) {
+            Some(summary) => {
+                let summary = summary.clone();
+                for (arg, summary_arg) in args.iter().zip(&summary.args) {
+                    self.propagate_links(*arg, *summary_arg);
+                }
+                summary
+            }
+            None => {
+                let net = net!(self);
+                let entry = net.add_place();
+                entry.name(net, format!("{} entry", fn_name))?;
+                let exit = net.add_place();
+                exit.name(net, format!("{} exit", fn_name))?;
+                // the storage of the locals is not tracked
+                // because the function can be active multiple times
+                let mut locals = Vec::new();
+                for (mir_local, decl) in self
+                    .tcx
+                    .optimized_mir(function)
+                    .local_decls
+                    .iter_enumerated()
+                {
+                    let name = format!("{}_{}: {}", fn_name, mir_local.index(), decl.ty);
+                    locals.push(Local::new_untracked(net, &name)?);
+                }
+                let data_return = locals.remove(0);
+                let arg_count = self.tcx.optimized_mir(function).arg_count;
+                let summary = Summary {
+                    entry,
+                    exit,
+                    data_return,
+                    args: locals.iter().take(arg_count).copied().collect(),
+                    call_sites: 0,
+                };
+                // the body is translated with the links of the first call site
+                for (arg, summary_arg) in args.iter().zip(&summary.args) {
+                    self.propagate_links(*arg, *summary_arg);
+                }
+                // insert before translating the body to find recursive calls
+                self.summaries.insert(key.clone(), summary.clone());
+                self.translate_body(
+                    function,
+                    substs,
+                    locals,
+                    summary.data_return,
+                    entry,
+                    exit,
+                    fn_name.clone(),
+                )?;
+                summary
+            }
+        };
+        let net = net!(self);
+        let call_site = net.add_place();
+        call_site.name(net, format!("{} call site {}", fn_name, summary.call_sites))?;
+        let t_call = net.add_transition();
+        t_call.name(net, format!("{} call {}", fn_name, summary.call_sites))?;
+        net.add_arc(start_place, t_call)?;
+        net.add_arc(t_call, summary.entry)?;
+        net.add_arc(t_call, call_site)?;
+        // the call reads the arguments of the caller
+        for arg in &args {
+            net.add_arc(arg.live_place, t_call)?;
+            net.add_arc(t_call, arg.live_place)?;
+        }
+        let t_return = net.add_transition();
+        t_return.name(net, format!("{} return {}", fn_name, summary.call_sites))?;
+        net.add_arc(summary.exit, t_return)?;
+        net.add_arc(call_site, t_return)?;
+        net.add_arc(t_return, return_flow)?;
+        self.summaries
+            .get_mut(&key)
+            .expect("summary not found")
+            .call_sites += 1;
+        self.propagate_links(summary.data_return, data_return);
+        Ok(())
+    }
+
+    fn translate_body(
+        &mut self,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
         args: Vec<Local>,
         data_return: Local,
         start_place: NodeRef,
@@ -185,7 +1103,10 @@ impl<'tcx> Translator<'tcx> {
         info!("\n\nENTERING function: {:?}", fn_name);
         if let Some(file) = &mut self.mir_dump {
             if !self.visited.contains(&function) {
-                write_mir_pretty(self.tcx, Some(function), file).unwrap();
+                if let Err(err) = write_mir_pretty(self.tcx, Some(function), file) {
+                    self.mir_dump_error = Some(err);
+                    self.mir_dump = None;
+                }
             }
         };
         self.visited.insert(function);
@@ -214,6 +1135,14 @@ impl<'tcx> Translator<'tcx> {
                 warn!("duplicate of promoted static");
             }
         }
+        // add missing static items
+        let mut used_statics = StaticItems {
+            def_ids: HashSet::new(),
+        };
+        used_statics.visit_body(body.unwrap_read_only());
+        for def_id in used_statics.def_ids {
+            self.static_item(def_id)?;
+        }
         let petri_function = Function::new(
             fn_name.clone(),
             body,
@@ -223,65 +1152,1187 @@ impl<'tcx> Translator<'tcx> {
             start_place,
             &const_memory,
             &static_memory,
+            &self.static_items,
             return_flow,
-            &mut self.mutex_list,
-            self.tcx,
+            self.config.ownership,
         )?;
         self.call_stack.push(petri_function);
+        for (mir_local, decl) in body.local_decls.iter_enumerated() {
+            let local = *function!(self)
+                .get_local(&mir_local)
+                .expect("local not found");
+            let location = self.source_location(
+                SpanKind::Local,
+                decl.source_info,
+                format!("_{}: {}", mir_local.index(), decl.ty),
+                None,
+                None,
+            );
+            let mut nodes = vec![
+                local.prenatal_place,
+                Some(local.live_place),
+                local.dead_place,
+            ];
+            if let Some(value) = local.value {
+                nodes.extend(&[Some(value.owned_place), Some(value.empty_place)]);
+            }
+            for node in nodes.into_iter().flatten() {
+                self.spans.insert(node, location.clone());
+            }
+        }
+        self.substs_stack.push(substs);
         self.visit_body(body.unwrap_read_only());
+        self.substs_stack.pop();
         self.call_stack.pop();
         info!("\nLEAVING function: {:?}\n", fn_name);
         Ok(())
     }
 
+    /// Where a node of the function on top of the call stack comes from.
+    fn source_location(
+        &self,
+        kind: SpanKind,
+        source_info: SourceInfo,
+        description: String,
+        block: Option<BasicBlock>,
+        statement: Option<usize>,
+    ) -> SourceLocation {
+        SourceLocation {
+            kind,
+            function: self
+                .call_stack
+                .peek()
+                .expect("empty call stack")
+                .name
+                .clone(),
+            block: block.map(|block| block.index()),
+            statement,
+            description,
+            span: self.tcx.sess.source_map().span_to_string(source_info.span),
+            callers: self.call_spans.iter().rev().cloned().collect(),
+            thread: self
+                .current_thread
+                .map(|thread| format!("Thread_{}", thread.index())),
+        }
+    }
+
     fn translate_unique(
         &mut self,
         function: DefId,
+        substs: SubstsRef<'tcx>,
         args: Vec<Local>,
+        const_args: Vec<Option<u128>>,
         data_return: Local,
         start_place: NodeRef,
         return_flow: NodeRef,
         fn_name: String,
     ) -> Result<()> {
+        // condvar operations are not bridged by a single transition
+        if Self::is_condvar_wait(&fn_name)
+            || fn_name == "std::sync::Condvar::notify_one"
+            || fn_name == "std::sync::Condvar::notify_all"
+        {
+            return self.translate_condvar(args, data_return, start_place, return_flow, fn_name);
+        }
+        // barriers and onces block until other threads arrive
+        if fn_name.contains("std::sync::Barrier::wait") {
+            return self.translate_barrier_wait(args, start_place, return_flow, fn_name);
+        }
+        if fn_name == "std::sync::Once::call_once"
+            || fn_name.contains("once_cell::sync::OnceCell::<T>::get_or_init")
+        {
+            return self.translate_once(substs, args, start_place, return_flow, fn_name);
+        }
+        // executors loop until the future is ready
+        if Self::is_executor(self.tcx, function, &fn_name) {
+            return self.translate_block_on(
+                function,
+                substs,
+                args,
+                data_return,
+                start_place,
+                return_flow,
+                fn_name,
+            );
+        }
+        // atomics branch on the stored value
+        if Self::is_atomic_operation(&fn_name) {
+            return self.translate_atomic(
+                args,
+                const_args,
+                data_return,
+                start_place,
+                return_flow,
+                fn_name,
+            );
+        }
+        // the same for channels
+        if Self::is_channel_operation(&fn_name) {
+            return self.translate_channel(
+                args,
+                const_args,
+                data_return,
+                start_place,
+                return_flow,
+                fn_name,
+            );
+        }
+        let lock_operation = self.config.lock_api.operation(&fn_name);
         let net = &mut self.net;
 
         // bridge the call
-        let t = net.add_transition();
-        t.name(net, fn_name.clone())?;
-        net.add_arc(start_place, t)?;
-        net.add_arc(t, return_flow)?;
+        let bridge = |net: &mut Net, name: String| -> Result<NodeRef> {
+            let t = net.add_transition();
+            t.name(net, name)?;
+            net.add_arc(start_place, t)?;
+            net.add_arc(t, return_flow)?;
+            Ok(t)
+        };
+        let t = bridge(net, fn_name.clone())?;
 
         match fn_name {
-            name if name.contains("std::sync::Mutex::<T>::new") => {
-                let mutex = *self
-                    .mutex_list
-                    .get_linked(data_return)
-                    .expect("mutex not found");
-                net.add_arc(mutex.uninitialized(&self.mutex_list), t)?;
-                net.add_arc(t, mutex.unlocked(&self.mutex_list))?;
+            // the allocation site of a mutex, every translated call creates a new one.
+            // A site that is reached from a loop creates several instances and every
+            // allocation initializes one of them, the last one stands for all further allocations.
+            _ if lock_operation == Some(LockOperation::Constructor) => {
+                let instances = if self.loop_calls.contains(&true) {
+                    self.config.loop_instances.max(1)
+                } else {
+                    1
+                };
+                self.mutex_list.unlink(data_return);
+                for index in 0..instances {
+                    let mutex = self.mutex_list.add(net)?;
+                    debug!("link '{:?}' to mutex '{:?}'", data_return, mutex);
+                    self.mutex_list.link(data_return, mutex);
+                    let t = match index {
+                        0 => t,
+                        _ => bridge(net, fn_name.clone())?,
+                    };
+                    net.add_arc(mutex.uninitialized(&self.mutex_list), t)?;
+                    net.add_arc(t, mutex.unlocked(&self.mutex_list))?;
+                    if index > 0 && index == instances - 1 {
+                        // reusing the initialized summary instance leaves it unchanged
+                        for state in &[
+                            mutex.unlocked(&self.mutex_list),
+                            mutex.locked(&self.mutex_list),
+                            mutex.dead(&self.mutex_list),
+                        ] {
+                            let reuse = bridge(net, format!("{} reuse", fn_name))?;
+                            net.add_arc(*state, reuse)?;
+                            net.add_arc(reuse, *state)?;
+                        }
+                    }
+                }
             }
-            name if name.contains("std::sync::Mutex::<T>::lock") => {
-                let mutex = *self
-                    .mutex_list
-                    .get_linked(*args.get(0).expect("no mutex lock arg found"))
-                    .expect("mutex not found");
-                self.mutex_list.add_guard(data_return, mutex);
-                net.add_arc(mutex.unlocked(&self.mutex_list), t)?;
-                net.add_arc(t, mutex.locked(&self.mutex_list))?;
+            // a lock that may point to several mutexes locks one of them
+            _ if lock_operation == Some(LockOperation::Acquire) => {
+                let mutexes =
+                    self.linked_mutexes(*args.get(0).expect("no mutex lock arg found"))?;
+                let net = &mut self.net;
+                for (index, mutex) in mutexes.into_iter().enumerate() {
+                    let t = match index {
+                        0 => t,
+                        _ => bridge(net, fn_name.clone())?,
+                    };
+                    let guard = self.mutex_list.add_guard(net, data_return, mutex)?;
+                    self.mutex_list.acquire(net, guard, t)?;
+                }
             }
-            name if name.contains("std::sync::Mutex::<T>::try_lock") => unimplemented!(),
-            _ => panic!("unhandled unique function"),
-        };
-        Ok(())
-    }
-}
-
-impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
-    fn visit_body(&mut self, body: ReadOnlyBodyAndCache<'_, 'tcx>) {
-        match body.phase {
-            MirPhase::Optimized => {}
-            _ => error!("tried to translate unoptimized MIR"),
-        }
+            name if lock_operation == Some(LockOperation::TryAcquire) => {
+                let mutexes =
+                    self.linked_mutexes(*args.get(0).expect("no mutex lock arg found"))?;
+                let net = &mut self.net;
+                for (index, mutex) in mutexes.into_iter().enumerate() {
+                    let t = match index {
+                        0 => t,
+                        _ => bridge(net, name.clone())?,
+                    };
+                    // Ok: same as lock
+                    let guard = self.mutex_list.add_guard(net, data_return, mutex)?;
+                    self.mutex_list.acquire(net, guard, t)?;
+                    // Err(WouldBlock): only possible while the mutex is locked
+                    would_block(
+                        net,
+                        &name,
+                        start_place,
+                        return_flow,
+                        mutex.locked(&self.mutex_list),
+                    )?;
+                }
+            }
+            name if lock_operation == Some(LockOperation::Release) => {
+                let released = *args.get(0).expect("no mutex release arg found");
+                if self.mutex_list.get_guards(released).is_some() {
+                    // the guard is moved into the call and not dropped by the caller
+                    self.mutex_list
+                        .release(net, released, start_place, t, return_flow)?;
+                } else if self.mutex_list.get_linked(released).is_some() {
+                    // raw unlock on the lock itself
+                    let mutexes = self.linked_mutexes(released)?;
+                    let net = &mut self.net;
+                    for (index, mutex) in mutexes.into_iter().enumerate() {
+                        let t = match index {
+                            0 => t,
+                            _ => bridge(net, name.clone())?,
+                        };
+                        net.add_arc(mutex.locked(&self.mutex_list), t)?;
+                        net.add_arc(t, mutex.unlocked(&self.mutex_list))?;
+                    }
+                } else {
+                    warn!("no mutex or guard found for {}", name);
+                }
+            }
+            name if name.contains("std::thread::spawn") => {
+                // fork: the call continues and the thread body starts concurrently
+                let thread = self.threads.add(net)?;
+                let thread_start = thread.start(&self.threads);
+                let thread_finished = thread.finished(&self.threads);
+                net.add_arc(t, thread_start)?;
+                self.threads.link(data_return, thread);
+                let thread_return =
+                    self.return_local(&format!("Thread_{} return", thread.index()))?;
+                let (closure, closure_substs) =
+                    Self::closure_in_substs(substs).expect("spawned closure not found");
+                let closure_arg = *args.get(0).expect("no closure arg found");
+                let parent_thread = self.current_thread.replace(thread);
+                self.translate(
+                    closure,
+                    closure_substs,
+                    vec![closure_arg],
+                    Vec::new(),
+                    thread_return,
+                    thread_start,
+                    thread_finished,
+                )?;
+                self.current_thread = parent_thread;
+            }
+            name if name.contains("std::thread::JoinHandle::<T>::join") => {
+                // join: wait until the thread body returned
+                let join_handle = *args.get(0).expect("no join handle arg found");
+                match self.threads.get_linked(join_handle) {
+                    Some(thread) => net.add_arc(thread.finished(&self.threads), t)?,
+                    None => warn!("join handle is not linked to a thread"),
+                }
+            }
+            name if name.contains("std::sync::RwLock::<T>::new") => {
+                let lock = self.rw_locks.add(net)?;
+                debug!("link '{:?}' to rwlock '{:?}'", data_return, lock);
+                self.rw_locks.link(data_return, lock);
+                net.add_arc(lock.uninitialized(&self.rw_locks), t)?;
+                net.add_arc(t, lock.writer_free(&self.rw_locks))?;
+            }
+            name if name.contains("std::sync::RwLock::<T>::read") => {
+                let lock = *self
+                    .rw_locks
+                    .get_linked(*args.get(0).expect("no rwlock arg found"))
+                    .expect("rwlock not found");
+                self.rw_locks.acquire_read(net, data_return, lock, t)?;
+            }
+            name if name.contains("std::sync::RwLock::<T>::write") => {
+                let lock = *self
+                    .rw_locks
+                    .get_linked(*args.get(0).expect("no rwlock arg found"))
+                    .expect("rwlock not found");
+                self.rw_locks.acquire_write(net, data_return, lock, t)?;
+            }
+            name if name.contains("std::sync::RwLock::<T>::try_read") => {
+                let lock = *self
+                    .rw_locks
+                    .get_linked(*args.get(0).expect("no rwlock arg found"))
+                    .expect("rwlock not found");
+                self.rw_locks.acquire_read(net, data_return, lock, t)?;
+                // Err(WouldBlock): only possible while a writer holds the lock
+                would_block(
+                    net,
+                    &name,
+                    start_place,
+                    return_flow,
+                    lock.writing(&self.rw_locks),
+                )?;
+            }
+            name if name.contains("std::sync::RwLock::<T>::try_write") => {
+                let lock = *self
+                    .rw_locks
+                    .get_linked(*args.get(0).expect("no rwlock arg found"))
+                    .expect("rwlock not found");
+                self.rw_locks.acquire_write(net, data_return, lock, t)?;
+                // Err(WouldBlock): only possible while a writer or a reader holds the lock
+                would_block(
+                    net,
+                    &name,
+                    start_place,
+                    return_flow,
+                    lock.writing(&self.rw_locks),
+                )?;
+                would_block(
+                    net,
+                    &name,
+                    start_place,
+                    return_flow,
+                    lock.readers(&self.rw_locks),
+                )?;
+            }
+            name if name == "std::sync::Condvar::new" => {
+                let condvar = self.condvars.add(net)?;
+                debug!("link '{:?}' to condvar '{:?}'", data_return, condvar);
+                self.condvars.link(data_return, condvar);
+                net.add_arc(condvar.uninitialized(&self.condvars), t)?;
+            }
+            name if name.contains("std::sync::Barrier::new") => {
+                let participants = match const_args.get(0) {
+                    Some(Some(participants)) => Some(std::cmp::max(*participants as usize, 1)),
+                    _ => {
+                        warn!(
+                            "unknown number of participants in {}, every wait may block",
+                            name
+                        );
+                        None
+                    }
+                };
+                let barrier = self.barriers.add(net, participants)?;
+                debug!("link '{:?}' to barrier '{:?}'", data_return, barrier);
+                self.barriers.link(data_return, barrier);
+                net.add_arc(barrier.uninitialized(&self.barriers), t)?;
+                if participants.is_some() {
+                    net.add_arc(t, barrier.arrived(&self.barriers, 0))?;
+                }
+            }
+            name if name.contains("std::sync::Once::new") => {
+                let once = self.onces.add(net)?;
+                debug!("link '{:?}' to once '{:?}'", data_return, once);
+                self.onces.link(data_return, once);
+            }
+            _ => panic!("unhandled unique function"),
+        };
+        Ok(())
+    }
+
+    fn translate_barrier_wait(
+        &mut self,
+        args: Vec<Local>,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let net = &mut self.net;
+        let barrier = match self
+            .barriers
+            .get_linked(*args.get(0).expect("no barrier arg found"))
+        {
+            Some(barrier) => *barrier,
+            None => {
+                warn!("barrier not found, {} does not block", fn_name);
+                let t = net.add_transition();
+                t.name(net, fn_name)?;
+                net.add_arc(start_place, t)?;
+                net.add_arc(t, return_flow)?;
+                return Ok(());
+            }
+        };
+        let blocked = net.add_place();
+        blocked.name(net, format!("{} blocked", fn_name))?;
+        let participants = match barrier.participants(&self.barriers) {
+            Some(participants) => participants,
+            // without the number of threads the wait may pass or block forever
+            None => {
+                let t = net.add_transition();
+                t.name(net, fn_name.clone())?;
+                net.add_arc(start_place, t)?;
+                net.add_arc(t, return_flow)?;
+                let t_block = net.add_transition();
+                t_block.name(net, format!("{} block", fn_name))?;
+                net.add_arc(start_place, t_block)?;
+                net.add_arc(t_block, blocked)?;
+                return Ok(());
+            }
+        };
+        // arrive and count up, the last arriving thread opens the barrier
+        for count in 0..participants {
+            let t = net.add_transition();
+            t.name(net, format!("{} arrive {}", fn_name, count + 1))?;
+            net.add_arc(start_place, t)?;
+            net.add_arc(barrier.arrived(&self.barriers, count), t)?;
+            net.add_arc(t, blocked)?;
+            if count + 1 < participants {
+                net.add_arc(t, barrier.arrived(&self.barriers, count + 1))?;
+            } else {
+                net.add_arc(t, barrier.leaving(&self.barriers, participants))?;
+            }
+        }
+        // leave and count down, the last leaving thread closes the barrier
+        for count in 1..=participants {
+            let t = net.add_transition();
+            t.name(net, format!("{} leave {}", fn_name, count))?;
+            net.add_arc(blocked, t)?;
+            net.add_arc(barrier.leaving(&self.barriers, count), t)?;
+            net.add_arc(t, return_flow)?;
+            if count > 1 {
+                net.add_arc(t, barrier.leaving(&self.barriers, count - 1))?;
+            } else {
+                net.add_arc(t, barrier.arrived(&self.barriers, 0))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn translate_once(
+        &mut self,
+        substs: SubstsRef<'tcx>,
+        args: Vec<Local>,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let once_arg = *args.get(0).expect("no once arg found");
+        let once = match self.onces.get_linked(once_arg) {
+            Some(once) => *once,
+            None => {
+                // e.g. a static that was not initialized by a call to `Once::new`
+                let once = self.onces.add(net!(self))?;
+                debug!("link '{:?}' to once '{:?}'", once_arg, once);
+                self.onces.link(once_arg, once);
+                once
+            }
+        };
+        let net = &mut self.net;
+        let closure_start = net.add_place();
+        let closure_end = net.add_place();
+        closure_end.name(net, format!("{} initialized", fn_name))?;
+        // the first caller runs the closure
+        let t_first = net.add_transition();
+        t_first.name(net, fn_name.clone())?;
+        net.add_arc(start_place, t_first)?;
+        net.add_arc(once.incomplete(&self.onces), t_first)?;
+        net.add_arc(t_first, once.running(&self.onces))?;
+        net.add_arc(t_first, closure_start)?;
+        let t_done = net.add_transition();
+        t_done.name(net, format!("{} complete", fn_name))?;
+        net.add_arc(closure_end, t_done)?;
+        net.add_arc(once.running(&self.onces), t_done)?;
+        net.add_arc(t_done, once.complete(&self.onces))?;
+        net.add_arc(t_done, return_flow)?;
+        // everybody else waits until the initialization is complete
+        let t_initialized = net.add_transition();
+        t_initialized.name(net, format!("{} already complete", fn_name))?;
+        net.add_arc(start_place, t_initialized)?;
+        net.add_arc(once.complete(&self.onces), t_initialized)?;
+        net.add_arc(t_initialized, once.complete(&self.onces))?;
+        net.add_arc(t_initialized, return_flow)?;
+
+        let closure_return = self.return_local(&format!("Once_{} closure return", once.index()))?;
+        let (closure, closure_substs) =
+            Self::closure_in_substs(substs).expect("initialization closure not found");
+        let closure_arg = *args.get(1).expect("no closure arg found");
+        self.translate(
+            closure,
+            closure_substs,
+            vec![closure_arg],
+            Vec::new(),
+            closure_return,
+            closure_start,
+            closure_end,
+        )
+    }
+
+    /// A single threaded executor that polls the future until it is ready.
+    /// The async body of the future is a state machine that is resumed by every poll.
+    fn translate_block_on(
+        &mut self,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+        args: Vec<Local>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        // the future is the last parameter, e.g. after the runtime of `Runtime::block_on`
+        let future_param = self.tcx.fn_sig(function).inputs().skip_binder().last();
+        let (future, future_ty) = match (args.last(), future_param) {
+            (Some(future), Some(future_ty)) => (*future, future_ty.subst(self.tcx, substs)),
+            _ => {
+                self.tcx.sess.err(&format!(
+                    "no future argument found for executor {}",
+                    fn_name
+                ));
+                let net = net!(self);
+                let t = net.add_transition();
+                t.name(net, fn_name)?;
+                net.add_arc(start_place, t)?;
+                net.add_arc(t, return_flow)?;
+                return Ok(());
+            }
+        };
+        let caller_substs = self.substs_stack.peek().copied();
+        let future_trait = self
+            .tcx
+            .lang_items()
+            .future_trait()
+            .expect("future trait not found");
+        let poll = self
+            .tcx
+            .associated_items(future_trait)
+            .find(|item| &*item.ident.as_str() == "poll")
+            .expect("Future::poll not found")
+            .def_id;
+        let poll_substs = self.tcx.mk_substs_trait(future_ty, &[]);
+
+        let net = net!(self);
+        let poll_start = net.add_place();
+        let poll_end = net.add_place();
+        poll_end.name(net, format!("{} polled", fn_name))?;
+        let t_start = net.add_transition();
+        t_start.name(net, fn_name.clone())?;
+        net.add_arc(start_place, t_start)?;
+        net.add_arc(t_start, poll_start)?;
+        // Poll::Ready
+        let t_ready = net.add_transition();
+        t_ready.name(net, format!("{} ready", fn_name))?;
+        net.add_arc(poll_end, t_ready)?;
+        net.add_arc(t_ready, return_flow)?;
+        // Poll::Pending, the executor polls again
+        let t_pending = net.add_transition();
+        t_pending.name(net, format!("{} pending", fn_name))?;
+        net.add_arc(poll_end, t_pending)?;
+        net.add_arc(t_pending, poll_start)?;
+
+        let context = Local::new(net, &format!("{} context", fn_name))?;
+        // `poll` takes the future as `Pin<&mut F>`, which refers to the same primitives
+        let pinned = Local::new(net, &format!("{} pinned future", fn_name))?;
+        self.propagate_links(future, pinned);
+        for (function, substs) in Self::resolve(self.tcx, caller_substs, poll, poll_substs) {
+            self.translate(
+                function,
+                substs,
+                vec![pinned, context],
+                Vec::new(),
+                data_return,
+                poll_start,
+                poll_end,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn translate_atomic(
+        &mut self,
+        args: Vec<Local>,
+        const_args: Vec<Option<u128>>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let is_bool = fn_name.contains("std::sync::atomic::AtomicBool::");
+        let operation = fn_name.rsplit("::").next().unwrap_or("").to_string();
+        // transitions from start to return flow (or the flow of an outcome)
+        let bridge = |net: &mut Net, name: String, to: NodeRef| -> Result<NodeRef> {
+            let t = net.add_transition();
+            t.name(net, name)?;
+            net.add_arc(start_place, t)?;
+            net.add_arc(t, to)?;
+            Ok(t)
+        };
+        let const_bool = |index: usize| match const_args.get(index) {
+            Some(Some(value)) => Some(*value != 0),
+            _ => None,
+        };
+        let both = vec![false, true];
+
+        if operation == "new" {
+            let net = &mut self.net;
+            let atomic = self.atomics.add(net, is_bool)?;
+            debug!("link '{:?}' to atomic '{:?}'", data_return, atomic);
+            self.atomics.link(data_return, atomic);
+            match atomic.get(&self.atomics) {
+                Atomic::Bool { .. } => {
+                    let values = const_bool(0).map_or(both, |value| vec![value]);
+                    for value in values {
+                        let t = bridge(net, format!("{} {}", fn_name, value), return_flow)?;
+                        net.add_arc(t, atomic.get(&self.atomics).bool_place(value))?;
+                    }
+                }
+                Atomic::Value(value) => {
+                    let t = bridge(net, fn_name, return_flow)?;
+                    net.add_arc(t, value)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let atomic_arg = *args.get(0).expect("no atomic arg found");
+        let atomic = match self.atomics.get_linked(atomic_arg) {
+            Some(atomic) => *atomic,
+            None => {
+                // e.g. a static that was not initialized by a call to `new`
+                warn!("atomic not found, assuming an initial value of zero");
+                let atomic = self.atomics.add_initialized(net!(self), is_bool)?;
+                debug!("link '{:?}' to atomic '{:?}'", atomic_arg, atomic);
+                self.atomics.link(atomic_arg, atomic);
+                atomic
+            }
+        };
+        let is_compare = operation.starts_with("compare_");
+        // `compare_exchange` returns `Ok` (discriminant 0) on success,
+        // the previous value of `compare_and_swap` is not followed
+        let (success_flow, failure_flow) = if operation.starts_with("compare_exchange") {
+            let outcome = Outcome::new(net!(self), &fn_name, 0)?;
+            debug!("link '{:?}' to outcome '{:?}'", data_return, outcome);
+            self.atomics.link_outcome(data_return, outcome);
+            let net = net!(self);
+            let success_flow = net.add_place();
+            success_flow.name(net, format!("{} succeeded", fn_name))?;
+            outcome.decide(net, success_flow, return_flow, true)?;
+            let failure_flow = net.add_place();
+            failure_flow.name(net, format!("{} failed", fn_name))?;
+            outcome.decide(net, failure_flow, return_flow, false)?;
+            (success_flow, failure_flow)
+        } else {
+            (return_flow, return_flow)
+        };
+        let net = &mut self.net;
+        let atomic = atomic.get(&self.atomics);
+
+        let value = match atomic {
+            Atomic::Bool { .. } => None,
+            Atomic::Value(value) => Some(value),
+        };
+        // only the order of accesses is modeled for non bool atomics
+        if let Some(value) = value {
+            let branches = if is_compare {
+                vec![
+                    (format!("{} success", fn_name), success_flow),
+                    (format!("{} failure", fn_name), failure_flow),
+                ]
+            } else {
+                vec![(fn_name, return_flow)]
+            };
+            for (name, to) in branches {
+                let t = bridge(net, name, to)?;
+                net.add_arc(value, t)?;
+                net.add_arc(t, value)?;
+            }
+            return Ok(());
+        }
+
+        // reads the current value without changing it
+        let load = |net: &mut Net, name: &str, values: &[bool], to: NodeRef| -> Result<()> {
+            for value in values {
+                let t = bridge(net, format!("{} {}", name, value), to)?;
+                net.add_arc(atomic.bool_place(*value), t)?;
+                net.add_arc(t, atomic.bool_place(*value))?;
+            }
+            Ok(())
+        };
+        // replaces any of the old values with any of the new values
+        let store =
+            |net: &mut Net, name: &str, old: &[bool], new: &[bool], to: NodeRef| -> Result<()> {
+                for old_value in old {
+                    for new_value in new {
+                        let name = format!("{} {} -> {}", name, old_value, new_value);
+                        let t = bridge(net, name, to)?;
+                        net.add_arc(atomic.bool_place(*old_value), t)?;
+                        net.add_arc(t, atomic.bool_place(*new_value))?;
+                    }
+                }
+                Ok(())
+            };
+        match operation.as_str() {
+            "load" => load(net, &fn_name, &both, return_flow)?,
+            "store" | "swap" => {
+                let new = const_bool(1).map_or(both.clone(), |value| vec![value]);
+                store(net, &fn_name, &both, &new, return_flow)?
+            }
+            "compare_and_swap" | "compare_exchange" | "compare_exchange_weak" => {
+                let current = const_bool(1).map_or(both.clone(), |value| vec![value]);
+                let new = const_bool(2).map_or(both.clone(), |value| vec![value]);
+                let success = format!("{} success", fn_name);
+                store(net, &success, &current, &new, success_flow)?;
+                let different: Vec<bool> = current.iter().map(|value| !value).collect();
+                let failure = format!("{} failure", fn_name);
+                load(net, &failure, &different, failure_flow)?;
+                if operation == "compare_exchange_weak" {
+                    // weak exchanges can fail even if the value matches
+                    let spurious = format!("{} spurious failure", fn_name);
+                    load(net, &spurious, &current, failure_flow)?;
+                }
+            }
+            // operations that are idempotent for a constant argument
+            "fetch_and" if const_bool(1) == Some(false) => {
+                store(net, &fn_name, &both, &[false], return_flow)?
+            }
+            "fetch_or" if const_bool(1) == Some(true) => {
+                store(net, &fn_name, &both, &[true], return_flow)?
+            }
+            "fetch_and" | "fetch_or" if const_bool(1).is_some() => {
+                load(net, &fn_name, &both, return_flow)?
+            }
+            _ => store(net, &fn_name, &both, &both, return_flow)?,
+        }
+        Ok(())
+    }
+
+    fn translate_channel(
+        &mut self,
+        args: Vec<Local>,
+        const_args: Vec<Option<u128>>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let net = &mut self.net;
+        // transitions from start to return flow
+        let bridge = |net: &mut Net, name: String| -> Result<NodeRef> {
+            let t = net.add_transition();
+            t.name(net, name)?;
+            net.add_arc(start_place, t)?;
+            net.add_arc(t, return_flow)?;
+            Ok(t)
+        };
+        if fn_name.contains("std::sync::mpsc::channel")
+            || fn_name.contains("std::sync::mpsc::sync_channel")
+        {
+            let bound = if fn_name.contains("std::sync::mpsc::sync_channel") {
+                match const_args.get(0) {
+                    Some(Some(bound)) => Some(*bound as usize),
+                    _ => {
+                        warn!(
+                            "unknown bound of {}, assuming an unbounded channel",
+                            fn_name
+                        );
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            let channel = self.channels.add(net, bound)?;
+            let sender = self.channels.add_sender(net, channel)?;
+            debug!("link '{:?}' to channel '{:?}'", data_return, channel);
+            self.channels.link(data_return, channel);
+            self.channels.link_sender(data_return, sender);
+            let t = bridge(net, fn_name)?;
+            net.add_arc(t, sender.alive(&self.channels))?;
+            net.add_arc(t, channel.receiver_alive(&self.channels))?;
+            return Ok(());
+        }
+        // the other operations are called on a sender or receiver
+        let channel = match args.get(0).and_then(|arg| self.channels.get_linked(*arg)) {
+            Some(channel) => *channel,
+            None => {
+                self.tcx
+                    .sess
+                    .err(&format!("no channel found for {}", fn_name));
+                bridge(net, fn_name)?;
+                return Ok(());
+            }
+        };
+        match fn_name {
+            name if name.contains("as std::clone::Clone>::clone") => {
+                let sender = self.channels.add_sender(net, channel)?;
+                self.channels.link(data_return, channel);
+                self.channels.link_sender(data_return, sender);
+                let t = bridge(net, name)?;
+                net.add_arc(t, sender.alive(&self.channels))?;
+            }
+            name if name.contains("std::sync::mpsc::Sender::<T>::send")
+                || name.contains("std::sync::mpsc::SyncSender::<T>::send") =>
+            {
+                let messages = channel.messages(&self.channels);
+                let receiver_dropped = channel.receiver_dropped(&self.channels);
+                match channel.capacity(&self.channels) {
+                    Capacity::Unbounded => {
+                        let t = bridge(net, name.clone())?;
+                        net.add_arc(t, messages)?;
+                    }
+                    // the message is put into any free slot
+                    Capacity::Bounded(slots) => {
+                        for (free, full) in slots {
+                            let t = bridge(net, name.clone())?;
+                            net.add_arc(free, t)?;
+                            net.add_arc(t, full)?;
+                        }
+                    }
+                    Capacity::Rendezvous { taken, .. } => {
+                        // wait until the receiver took the message
+                        let sending = net.add_place();
+                        sending.name(net, format!("{} sending", name))?;
+                        let t = net.add_transition();
+                        t.name(net, name.clone())?;
+                        net.add_arc(start_place, t)?;
+                        net.add_arc(t, sending)?;
+                        net.add_arc(t, messages)?;
+                        let t_taken = net.add_transition();
+                        t_taken.name(net, format!("{} taken", name))?;
+                        net.add_arc(sending, t_taken)?;
+                        net.add_arc(taken, t_taken)?;
+                        net.add_arc(t_taken, return_flow)?;
+                        let t_abort = net.add_transition();
+                        t_abort.name(net, format!("{} disconnected", name))?;
+                        net.add_arc(sending, t_abort)?;
+                        net.add_arc(t_abort, return_flow)?;
+                        net.add_arc(receiver_dropped, t_abort)?;
+                        net.add_arc(t_abort, receiver_dropped)?;
+                    }
+                }
+                // Err(SendError): the receiver is gone
+                would_block(net, &name, start_place, return_flow, receiver_dropped)?;
+            }
+            name if name.contains("std::sync::mpsc::SyncSender::<T>::try_send") => {
+                match channel.capacity(&self.channels) {
+                    Capacity::Bounded(slots) => {
+                        for (free, full) in slots {
+                            let t = bridge(net, name.clone())?;
+                            net.add_arc(free, t)?;
+                            net.add_arc(t, full)?;
+                        }
+                    }
+                    // Ok: a receiver is already waiting and gets the message directly
+                    Capacity::Rendezvous {
+                        receiving,
+                        handed_over,
+                        ..
+                    } => {
+                        let t = bridge(net, name.clone())?;
+                        net.add_arc(receiving, t)?;
+                        net.add_arc(t, handed_over)?;
+                    }
+                    Capacity::Unbounded => {}
+                }
+                // Err(Full) cannot be tested and is always possible
+                bridge(net, format!("{} Full", name))?;
+                would_block(
+                    net,
+                    &name,
+                    start_place,
+                    return_flow,
+                    channel.receiver_dropped(&self.channels),
+                )?;
+            }
+            name if name.contains("std::sync::mpsc::Receiver::<T>::recv")
+                || name.contains("std::sync::mpsc::Receiver::<T>::try_recv") =>
+            {
+                let messages = channel.messages(&self.channels);
+                let is_try = name.contains("std::sync::mpsc::Receiver::<T>::try_recv");
+                let capacity = channel.capacity(&self.channels);
+                // Err(RecvError): all senders are gone (tested in `finalize`)
+                let t_disconnected = match &capacity {
+                    Capacity::Unbounded => {
+                        let t = bridge(net, name.clone())?;
+                        net.add_arc(messages, t)?;
+                        // a net cannot test an unbounded queue for emptiness, so this is
+                        // an over-approximation: without senders `recv` may return `Err`
+                        // although messages are left, which are then never received
+                        bridge(net, format!("{} disconnected", name))?
+                    }
+                    // the message is taken from any full slot
+                    Capacity::Bounded(slots) => {
+                        for (free, full) in slots {
+                            let t = bridge(net, name.clone())?;
+                            net.add_arc(*full, t)?;
+                            net.add_arc(t, *free)?;
+                        }
+                        // only if no message is left
+                        let t = bridge(net, format!("{} disconnected", name))?;
+                        for (free, _) in slots {
+                            net.add_arc(*free, t)?;
+                            net.add_arc(t, *free)?;
+                        }
+                        t
+                    }
+                    // only a sender that already waits can be received from
+                    Capacity::Rendezvous { taken, .. } if is_try => {
+                        let t = bridge(net, name.clone())?;
+                        net.add_arc(messages, t)?;
+                        net.add_arc(t, *taken)?;
+                        bridge(net, format!("{} disconnected", name))?
+                    }
+                    // wait for a sender or a message of `try_send`
+                    Capacity::Rendezvous {
+                        taken,
+                        receiving,
+                        handed_over,
+                    } => {
+                        let t_wait = net.add_transition();
+                        t_wait.name(net, format!("{} waiting", name))?;
+                        net.add_arc(start_place, t_wait)?;
+                        net.add_arc(t_wait, *receiving)?;
+                        let t = net.add_transition();
+                        t.name(net, name.clone())?;
+                        net.add_arc(*receiving, t)?;
+                        net.add_arc(messages, t)?;
+                        net.add_arc(t, *taken)?;
+                        net.add_arc(t, return_flow)?;
+                        let t_handed_over = net.add_transition();
+                        t_handed_over.name(net, format!("{} handed over", name))?;
+                        net.add_arc(*receiving, t_handed_over)?;
+                        net.add_arc(*handed_over, t_handed_over)?;
+                        net.add_arc(t_handed_over, return_flow)?;
+                        let t_disconnected = net.add_transition();
+                        t_disconnected.name(net, format!("{} disconnected", name))?;
+                        net.add_arc(*receiving, t_disconnected)?;
+                        net.add_arc(t_disconnected, return_flow)?;
+                        t_disconnected
+                    }
+                };
+                self.channels.add_disconnected(channel, t_disconnected);
+                // Err(Empty) and Err(Timeout) cannot be tested and are always possible
+                if is_try || name.contains("std::sync::mpsc::Receiver::<T>::recv_timeout") {
+                    bridge(net, format!("{} empty", name))?;
+                }
+            }
+            name => {
+                self.tcx
+                    .sess
+                    .err(&format!("unhandled channel function {}", name));
+                bridge(net, name)?;
+            }
+        };
+        Ok(())
+    }
+
+    fn translate_condvar(
+        &mut self,
+        args: Vec<Local>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        fn_name: String,
+    ) -> Result<()> {
+        let net = &mut self.net;
+        let condvar = *self
+            .condvars
+            .get_linked(*args.get(0).expect("no condvar arg found"))
+            .expect("condvar not found");
+        match fn_name {
+            name if Self::is_condvar_wait(&name) => {
+                let guards = self
+                    .mutex_list
+                    .get_guards(*args.get(1).expect("no guard arg found"))
+                    .expect("guard not found")
+                    .clone();
+                let waiter = self.condvars.waiter(net, condvar, self.current_thread)?;
+                let rechecks = name.ends_with("_while") || name.ends_with("_until");
+                // the result of the timeout variants tells whether the wait timed out,
+                // a switch on it reads the outcome (`wait_timeout_ms` returns the negation)
+                let (exit, timeout_exit) = if name.contains("std::sync::Condvar::wait_timeout") {
+                    let timed_out_value = if name.ends_with("_ms") { 0 } else { 1 };
+                    let outcome = Outcome::new(net, &format!("{} timeout", name), timed_out_value)?;
+                    debug!("link '{:?}' to outcome '{:?}'", data_return, outcome);
+                    self.atomics.link_outcome(data_return, outcome);
+                    let notified = net.add_place();
+                    notified.name(net, format!("{} notified", name))?;
+                    outcome.decide(net, notified, return_flow, false)?;
+                    let timed_out = net.add_place();
+                    timed_out.name(net, format!("{} timed out", name))?;
+                    outcome.decide(net, timed_out, return_flow, true)?;
+                    (notified, Some(timed_out))
+                } else {
+                    (return_flow, None)
+                };
+                // the `*_while` variants check the predicate before every wait and after every
+                // wake up, its data is not followed, so it may hold or not each time
+                let (wait_from, woken, unnotified) = if rechecks {
+                    let holds = net.add_place();
+                    holds.name(net, format!("{} condition holds", name))?;
+                    let t_holds = net.add_transition();
+                    t_holds.name(net, format!("{} condition holds", name))?;
+                    net.add_arc(start_place, t_holds)?;
+                    net.add_arc(t_holds, holds)?;
+                    let t_fails = net.add_transition();
+                    t_fails.name(net, format!("{} condition fails", name))?;
+                    net.add_arc(start_place, t_fails)?;
+                    net.add_arc(t_fails, exit)?;
+                    // the timeout may elapse while the predicate still holds
+                    if let Some(timed_out) = timeout_exit {
+                        let t_timeout = net.add_transition();
+                        t_timeout.name(net, format!("{} timeout elapsed", name))?;
+                        net.add_arc(start_place, t_timeout)?;
+                        net.add_arc(t_timeout, timed_out)?;
+                    }
+                    (holds, start_place, start_place)
+                } else {
+                    (start_place, exit, timeout_exit.unwrap_or(exit))
+                };
+                // a guard that may hold one of several mutexes waits on the one it holds
+                for guard in guards {
+                    // the returned guard holds the same mutex
+                    self.mutex_list.link_guard(data_return, guard);
+                    let blocked = net.add_place();
+                    blocked.name(net, format!("{} blocked", name))?;
+                    // release the mutex and wait
+                    let t_wait = net.add_transition();
+                    t_wait.name(net, name.clone())?;
+                    net.add_arc(wait_from, t_wait)?;
+                    net.add_arc(waiter.not_waiting, t_wait)?;
+                    net.add_arc(t_wait, waiter.waiting)?;
+                    net.add_arc(t_wait, blocked)?;
+                    self.mutex_list.unlock(net, guard, t_wait)?;
+                    // reacquire the mutex after a notification
+                    let t_wake = net.add_transition();
+                    t_wake.name(net, format!("{} wake up", name))?;
+                    net.add_arc(blocked, t_wake)?;
+                    net.add_arc(waiter.notified, t_wake)?;
+                    net.add_arc(t_wake, woken)?;
+                    self.mutex_list.acquire(net, guard, t_wake)?;
+                    // a timeout is a wake up without notification
+                    if self.config.spurious_wakeups || timeout_exit.is_some() {
+                        let t_spurious = net.add_transition();
+                        t_spurious.name(net, format!("{} wake up without notification", name))?;
+                        net.add_arc(blocked, t_spurious)?;
+                        net.add_arc(waiter.waiting, t_spurious)?;
+                        net.add_arc(t_spurious, waiter.not_waiting)?;
+                        net.add_arc(t_spurious, unnotified)?;
+                        self.mutex_list.acquire(net, guard, t_spurious)?;
+                    }
+                }
+            }
+            name if name == "std::sync::Condvar::notify_one" => {
+                // the notification is lost if nobody waits
+                let t_lost = net.add_transition();
+                t_lost.name(net, format!("{} lost", name))?;
+                net.add_arc(start_place, t_lost)?;
+                net.add_arc(t_lost, return_flow)?;
+                self.condvars
+                    .add_notify_one(condvar, start_place, return_flow, t_lost);
+            }
+            name if name == "std::sync::Condvar::notify_all" => {
+                let broadcasting = net.add_place();
+                broadcasting.name(net, format!("{} broadcasting", name))?;
+                let t_start = net.add_transition();
+                t_start.name(net, name.clone())?;
+                net.add_arc(start_place, t_start)?;
+                net.add_arc(t_start, broadcasting)?;
+                // finished when all waiters are woken up
+                let t_finished = net.add_transition();
+                t_finished.name(net, format!("{} finished", name))?;
+                net.add_arc(broadcasting, t_finished)?;
+                net.add_arc(t_finished, return_flow)?;
+                self.condvars
+                    .add_notify_all(condvar, broadcasting, t_finished);
+            }
+            _ => panic!("unhandled condvar function"),
+        };
+        Ok(())
+    }
+
+    /// Drops the value at the location and releases the primitives it is linked to.
+    /// Returns the drop transitions (the regular one and, if present, the unwinding one).
+    /// With `replace` the location owns a (new) value afterwards.
+    fn translate_drop(
+        &mut self,
+        location: &Place<'tcx>,
+        target: BasicBlock,
+        unwind: Option<BasicBlock>,
+        replace: bool,
+    ) -> Result<Vec<NodeRef>> {
+        // a guard has a Drop implementation, so going out of scope always ends up here
+        // (and never just in a StorageDead)
+        let dropped = function!(self).place_to_local(location);
+        // dropping a part of a local does not end the ownership of the whole local
+        let dropped_value = if location.projection.is_empty() && !replace {
+            function!(self).place_to_value(location)
+        } else {
+            None
+        };
+        // guards stored in a struct are released with it
+        let mut dropped_locals = vec![dropped];
+        let (root, prefix) = self.fields.key_of(dropped);
+        dropped_locals.extend(
+            self.fields
+                .descendants(root, &prefix)
+                .into_iter()
+                .map(|(_, field)| field),
+        );
+        // senders and receivers share their links
+        let channel_end = {
+            let function = self.call_stack.peek().expect("peeked empty stack");
+            let dropped_ty = location.ty(function.mir_body.local_decls(), self.tcx).ty;
+            match dropped_ty.sort_string(self.tcx).as_ref() {
+                "struct `std::sync::mpsc::Sender`" | "struct `std::sync::mpsc::SyncSender`" => {
+                    Some(true)
+                }
+                "struct `std::sync::mpsc::Receiver`" => Some(false),
+                _ => None,
+            }
+        };
+        let net = net!(self);
+        let source = function!(self).function_call_start_place()?;
+        let transitions = function!(self).drop(net, target, unwind, dropped_value)?;
+        let mut targets = vec![target];
+        targets.extend(unwind);
+        for (t, target) in transitions.iter().zip(targets) {
+            let target = function!(self).get_basic_block_start(net, target)?;
+            for dropped in &dropped_locals {
+                self.mutex_list.release(net, *dropped, source, *t, target)?;
+                self.rw_locks.release(net, *dropped, source, *t, target)?;
+            }
+            if let Some(is_sender) = channel_end {
+                self.channels
+                    .release(net, dropped, is_sender, source, *t, target)?;
+            }
+        }
+        Ok(transitions)
+    }
+}
+
+/// Collects the static items that are used in a body.
+struct StaticItems {
+    def_ids: HashSet<DefId>,
+}
+
+impl<'tcx> Visitor<'tcx> for StaticItems {
+    fn visit_place_base(
+        &mut self,
+        base: &PlaceBase<'tcx>,
+        _context: PlaceContext,
+        _location: Location,
+    ) {
+        if let PlaceBase::Static(statik) = base {
+            if let StaticKind::Static = statik.kind {
+                self.def_ids.insert(statik.def_id);
+            }
+        }
+    }
+}
+
+/// Collects the places that are used in a statement or terminator.
+struct Places<'tcx> {
+    places: Vec<Place<'tcx>>,
+}
+
+impl<'tcx> Visitor<'tcx> for Places<'tcx> {
+    fn visit_place(&mut self, place: &Place<'tcx>, _context: PlaceContext, _location: Location) {
+        self.places.push(place.clone());
+    }
+}
+
+/// Adds the failure branch of a `try_*` function that can only be taken
+/// if the `condition` place is marked (e.g. the lock is held by someone else).
+fn would_block(
+    net: &mut Net,
+    name: &str,
+    start_place: NodeRef,
+    return_flow: NodeRef,
+    condition: NodeRef,
+) -> Result<()> {
+    let t = net.add_transition();
+    t.name(net, format!("{} WouldBlock", name))?;
+    net.add_arc(start_place, t)?;
+    net.add_arc(t, return_flow)?;
+    net.add_arc(condition, t)?;
+    net.add_arc(t, condition)?;
+    Ok(())
+}
+
+impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
+    fn visit_body(&mut self, body: ReadOnlyBodyAndCache<'_, 'tcx>) {
+        match body.phase {
+            MirPhase::Optimized => {}
+            _ => error!("tried to translate unoptimized MIR"),
+        }
         self.super_body(body);
     }
 
@@ -290,6 +2341,21 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
         function!(self)
             .activate_block(net!(self), block)
             .expect("unable to activate basic");
+        let start_place = function!(self)
+            .get_basic_block_start(net!(self), block)
+            .expect("unable to find basic block start");
+        let source_info = match data.statements.first() {
+            Some(statement) => statement.source_info,
+            None => data.terminator().source_info,
+        };
+        let location = self.source_location(
+            SpanKind::Block,
+            source_info,
+            format!("bb {}", block.index()),
+            Some(block),
+            None,
+        );
+        self.spans.insert(start_place, location);
         self.super_basic_block_data(block, data)
     }
 
@@ -297,21 +2363,95 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
         let function = function!(self);
 
         let mut locals = Vec::new();
+        let mut fields: Vec<(FieldPath, Local)> = Vec::new();
+        let mut pointer_targets = Vec::new();
+        let mut referenced = None;
+        // with the ownership model the primitives move with the value
+        let moved = match rvalue {
+            Rvalue::Use(Operand::Move(moved_place))
+                if self.config.ownership && moved_place.projection.is_empty() =>
+            {
+                Some(function.place_to_local(moved_place))
+            }
+            _ => None,
+        };
         match rvalue {
+            Rvalue::Cast(CastKind::Pointer(PointerCast::ReifyFnPointer), operand, _)
+            | Rvalue::Cast(CastKind::Pointer(PointerCast::ClosureFnPointer(_)), operand, _) => {
+                let ty = operand.ty(function.mir_body.local_decls(), self.tcx);
+                pointer_targets =
+                    Self::pointer_targets(self.tcx, self.substs_stack.peek().copied(), ty);
+                locals.push(function.op_to_local(operand))
+            }
             Rvalue::Use(operand) | Rvalue::Repeat(operand, _) | Rvalue::Cast(_, operand, _) => {
                 locals.push(function.op_to_local(operand))
             }
-            Rvalue::Ref(_, _, place) => locals.push(function.place_to_local(place)),
+            Rvalue::Ref(_, _, referenced_place) | Rvalue::AddressOf(_, referenced_place) => {
+                locals.push(function.place_to_local(referenced_place));
+                referenced = Some(referenced_place);
+            }
             Rvalue::Discriminant(place) => locals.push(function.place_to_local(place)),
-            Rvalue::AddressOf(_, _) => locals.push(function.place_to_local(place)),
-            Rvalue::Aggregate(_, _) => unimplemented!(),
+            // e.g. the captured upvars of a closure
+            Rvalue::Aggregate(kind, operands) => {
+                let variant = match **kind {
+                    AggregateKind::Adt(adt_def, variant, _, _, _) if adt_def.is_enum() => {
+                        vec![FieldStep::Variant(variant.index())]
+                    }
+                    _ => Vec::new(),
+                };
+                for (index, operand) in operands.iter().enumerate() {
+                    let index = match **kind {
+                        AggregateKind::Adt(_, _, _, _, Some(active_field)) => active_field,
+                        AggregateKind::Array(_) => {
+                            locals.push(function.op_to_local(operand));
+                            continue;
+                        }
+                        _ => index,
+                    };
+                    let mut path = variant.clone();
+                    path.push(FieldStep::Field(index));
+                    fields.push((path, function.op_to_local(operand)))
+                }
+            }
             _ => {}
         }
 
+        let target = function.place_to_local(place);
+        // the assigned local forgets the primitives it pointed to before
+        if place.projection.is_empty() && !self.static_items.values().any(|item| *item == target) {
+            self.unlink(target);
+        }
         for local in locals {
-            if let Some(mutex) = self.mutex_list.is_linked(local) {
-                debug!("link '{:?}' to mutex '{:?}'", place, mutex);
-                self.mutex_list.link(function.place_to_local(place), *mutex)
+            self.propagate_links(local, target);
+        }
+        if let Some(moved) = moved {
+            if moved != target && moved.value.is_some() {
+                debug!("move the links of '{:?}' to '{:?}'", moved, target);
+                self.unlink(moved);
+            }
+        }
+        if let Some(referenced) = referenced {
+            if let Some((base, name)) = self.place_base(referenced) {
+                let pointee = self
+                    .place_part(base, referenced, &name)
+                    .expect("unable to add pointee");
+                debug!("link '{:?}' to pointee '{:?}'", target, pointee);
+                self.pointees.insert(target, pointee);
+            }
+        }
+        for (path, local) in fields {
+            let field = self
+                .field(target, &path, "aggregate")
+                .expect("unable to add field");
+            self.propagate_links(local, field);
+        }
+        for pointer_target in pointer_targets {
+            if !self.address_taken.contains(&pointer_target) {
+                self.address_taken.push(pointer_target);
+            }
+            let targets = self.fn_pointers.entry(target).or_insert_with(Vec::new);
+            if !targets.contains(&pointer_target) {
+                targets.push(pointer_target);
             }
         }
         self.super_assign(place, rvalue, location);
@@ -319,16 +2459,34 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
 
     fn visit_statement(&mut self, statement: &Statement<'tcx>, location: Location) {
         trace!("{:?}: ", statement.kind);
-        function!(self)
+        let mut places = Places { places: Vec::new() };
+        places.visit_statement(statement, location);
+        self.add_fields(places.places)
+            .expect("unable to add fields");
+        let nodes = function!(self)
             .add_statement(net!(self), statement)
             .expect("unable to add statement");
+        let source = self.source_location(
+            SpanKind::Statement,
+            statement.source_info,
+            format!("{:?}", statement.kind),
+            Some(location.block),
+            Some(location.statement_index),
+        );
+        for node in nodes {
+            self.spans.insert(node, source.clone());
+        }
         self.super_statement(statement, location);
     }
 
     fn visit_terminator_kind(&mut self, kind: &TerminatorKind<'tcx>, location: Location) {
         trace!("{:?}", kind);
+        let mut places = Places { places: Vec::new() };
+        places.visit_terminator_kind(kind, location);
+        self.add_fields(places.places)
+            .expect("unable to add fields");
 
-        // check mutex links
+        // check mutex and thread links
         match kind {
             Call {
                 func: _,
@@ -336,14 +2494,11 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
                 ref destination,
                 ..
             } => {
-                for arg in args {
-                    let local = function!(self).op_to_local(arg);
-                    if let Some((place, _)) = destination {
-                        if let Some(mutex) = self.mutex_list.is_linked(local) {
-                            debug!("link '{:?}' to mutex '{:?}'", place, mutex);
-                            self.mutex_list
-                                .link(function!(self).place_to_local(place), *mutex)
-                        }
+                if let Some((place, _)) = destination {
+                    let target = function!(self).place_to_local(place);
+                    for arg in args {
+                        let local = function!(self).op_to_local(arg);
+                        self.propagate_links(local, target);
                     }
                 }
             }
@@ -355,6 +2510,24 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
         function!(self)
             .finish_basic_block(net)
             .expect("cannot end statement block");
+        // the transitions of the terminator consume from the end place of the block
+        let source_info = *function!(self).mir_body.source_info(location);
+        let end_place = function!(self)
+            .function_call_start_place()
+            .expect("no active basic block");
+        let source = self.source_location(
+            SpanKind::Terminator,
+            source_info,
+            format!("{:?}", kind),
+            Some(location.block),
+            None,
+        );
+        self.spans.insert(end_place, source);
+        self.call_spans
+            .push(self.tcx.sess.source_map().span_to_string(source_info.span));
+        let in_loop = function!(self).in_loop(location.block);
+        self.loop_calls.push(in_loop);
+        let net = net!(self);
         match kind {
             Return => {
                 // trace!("Return");
@@ -369,13 +2542,24 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
             }
 
             SwitchInt {
-                discr: _,
+                discr,
                 switch_ty: _,
-                values: _,
+                values,
                 targets,
-            } => function!(self)
-                .switch_int(net, targets)
-                .expect("switch int failed"),
+            } => {
+                let transitions = function!(self)
+                    .switch_int(net, targets)
+                    .expect("switch int failed");
+                // branches on the result of a compare exchange follow its outcome
+                let discr = function!(self).op_to_local(discr);
+                if let Some(outcome) = self.atomics.get_outcome(discr).copied() {
+                    for (index, transition) in transitions.into_iter().enumerate() {
+                        outcome
+                            .read(net!(self), transition, values.get(index).copied(), values)
+                            .expect("unable to read outcome");
+                    }
+                }
+            }
 
             Call {
                 ref func,
@@ -399,71 +2583,194 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
                         Operand::Constant(ref constant) => &constant.literal.ty,
                     }
                 };
-                let function = match sty.kind {
-                    ty::FnPtr(_) => {
-                        error!("Function pointers are not supported");
-                        panic!("")
+                let (callees, via_pointer) = match sty.kind {
+                    ty::FnPtr(signature) => {
+                        let pointer = match func {
+                            Operand::Copy(place) | Operand::Move(place) => {
+                                Some(function!(self).place_to_local(place))
+                            }
+                            Operand::Constant(_) => None,
+                        };
+                        (self.fn_pointer_callees(pointer, signature), true)
                     }
-                    ty::FnDef(def_id, _) => def_id,
+                    ty::FnDef(def_id, substs) => (
+                        Self::resolve(self.tcx, self.substs_stack.peek().copied(), def_id, substs),
+                        false,
+                    ),
                     _ => {
                         error!("Expected function definition or pointer but got: {:?}", sty);
                         panic!("")
                     }
                 };
-                if !Self::is_panic(self.tcx, function) {
-                    if self.tcx.is_foreign_item(function) || !self.tcx.is_mir_available(function) {
-                        info!("emulating mir-less item {:?}", function);
-                        function!(self)
+                if callees.is_empty() {
+                    warn!("no target found for function pointer {:?}", func);
+                    match destination {
+                        Some(_) => function!(self)
                             .emulate_foreign(
-                                net,
-                                &self.tcx.def_path_str(function),
+                                net!(self),
+                                "function pointer",
                                 args,
                                 destination,
                                 *cleanup,
                                 self.unwind_abort_place,
+                                None,
                             )
-                            .expect("unknown foreign item");
-                    } else {
-                        let start_place = function!(self)
-                            .function_call_start_place()
-                            .expect("Unable to infer start place of function call")
-                            .clone();
-                        let (return_place, return_block) = destination.as_ref().expect(&format!(
-                            "diverging function: {}",
-                            self.tcx.def_path_str(function),
-                        ));
-                        let data_return = *function!(self)
-                            .get_local(
-                                &return_place
-                                    .local_or_deref_local()
-                                    .expect("deref return place failed"),
+                            .expect("unknown foreign item"),
+                        // a diverging callee never returns, e.g. it panics or exits
+                        None => function!(self)
+                            .handle_panic(net!(self), self.unwind_abort_place)
+                            .expect("panic handling error"),
+                    }
+                }
+                // calls on trait objects and function pointers
+                // branch nondeterministically into every callee
+                for (function, substs) in callees {
+                    let net = net!(self);
+                    if !Self::is_panic(self.tcx, function) {
+                        if self.tcx.is_foreign_item(function)
+                            || !self.tcx.is_mir_available(function)
+                        {
+                            info!("emulating mir-less item {:?}", function);
+                            let name = self.tcx.def_path_str(function);
+                            // the flag of the returned tuple is true on success
+                            let outcome = match destination {
+                                Some((place, _))
+                                    if name.contains("std::intrinsics::atomic_cxchg") =>
+                                {
+                                    let outcome =
+                                        Outcome::new(net, &name, 1).expect("unable to add outcome");
+                                    let result = function!(self).place_to_local(place);
+                                    self.atomics.link_outcome(result, outcome);
+                                    Some(outcome)
+                                }
+                                _ => None,
+                            };
+                            function!(self)
+                                .emulate_foreign(
+                                    net!(self),
+                                    &name,
+                                    args,
+                                    destination,
+                                    *cleanup,
+                                    self.unwind_abort_place,
+                                    outcome.as_ref(),
+                                )
+                                .expect("unknown foreign item");
+                        } else {
+                            let start_place = function!(self)
+                                .function_call_start_place()
+                                .expect("Unable to infer start place of function call")
+                                .clone();
+                            let (return_place, return_block) =
+                                destination.as_ref().expect(&format!(
+                                    "diverging function: {}",
+                                    self.tcx.def_path_str(function),
+                                ));
+                            let data_return = *function!(self)
+                                .get_local(
+                                    &return_place
+                                        .local_or_deref_local()
+                                        .expect("deref return place failed"),
+                                )
+                                .expect("return local not found");
+                            let const_args = args
+                                .iter()
+                                .map(|operand| Self::const_operand(self.tcx, operand))
+                                .collect();
+                            let stack_top = function!(self); // needed in the closure
+                            let mut args: Vec<Local> = args
+                                .iter()
+                                .map(|operand| stack_top.op_to_local(operand))
+                                .collect();
+                            if self.tcx.is_closure(function) {
+                                if via_pointer {
+                                    // closures without captures can be cast to pointers
+                                    let name = "closure environment";
+                                    let mut environment = Local::new(net, name)
+                                        .expect("cannot create closure environment");
+                                    if self.config.ownership {
+                                        environment
+                                            .add_value(net!(self), name, true)
+                                            .expect("cannot create closure environment");
+                                    }
+                                    args.insert(0, environment);
+                                } else if args.len() == 2 {
+                                    // the closure trait methods get the arguments in a tuple,
+                                    // but the closure body takes them one by one
+                                    let tuple = args.pop().expect("no argument tuple");
+                                    let arg_count = self.tcx.optimized_mir(function).arg_count;
+                                    for index in 0..arg_count - 1 {
+                                        let arg = self
+                                            .field(tuple, &[FieldStep::Field(index)], "argument")
+                                            .expect("unable to add closure argument");
+                                        args.push(arg);
+                                    }
+                                }
+                            }
+                            let return_place = function!(self)
+                                .return_flow(net!(self), &(return_place.clone(), *return_block))
+                                .expect("cannot find return block");
+                            self.translate(
+                                function,
+                                substs,
+                                args,
+                                const_args,
+                                data_return,
+                                start_place,
+                                return_place,
                             )
-                            .expect("return local not found");
-                        let stack_top = function!(self); // needed in the closure
-                        let args = args
-                            .iter()
-                            .map(|operand| stack_top.op_to_local(operand))
-                            .collect();
-                        let return_place = function!(self)
-                            .get_basic_block_start(net, *return_block)
-                            .expect("cannot find return block");
-                        self.translate(function, args, data_return, start_place, return_place)
                             .expect("translation error");
+                        }
+                    } else {
+                        function!(self)
+                            .handle_panic(net, self.unwind_abort_place)
+                            .expect("panic handling error");
                     }
-                } else {
-                    function!(self)
-                        .handle_panic(net, self.unwind_abort_place)
-                        .expect("panic handling error");
                 }
             }
 
             Drop {
-                location: _,
+                ref location,
                 target,
                 unwind,
-            } => function!(self)
-                .drop(net, *target, *unwind)
-                .expect("drop failed"),
+            } => {
+                self.translate_drop(location, *target, *unwind, false)
+                    .expect("drop failed");
+            }
+
+            // drop the old value and assign the new one
+            DropAndReplace {
+                ref location,
+                ref value,
+                target,
+                unwind,
+            } => {
+                let transitions = self
+                    .translate_drop(location, *target, *unwind, true)
+                    .expect("drop failed");
+                let replaced = function!(self).place_to_local(location);
+                let new_value = function!(self).op_to_local(value);
+                // drop elaboration only leaves this for initialized locations,
+                // so the ownership of the replaced local does not change
+                let moved_value = match value {
+                    Operand::Move(place) if place.projection.is_empty() => {
+                        function!(self).place_to_value(place)
+                    }
+                    _ => None,
+                };
+                let net = net!(self);
+                for t in transitions {
+                    for local in &[replaced, new_value] {
+                        net.add_arc(local.live_place, t).expect("arc error");
+                        net.add_arc(t, local.live_place).expect("arc error");
+                    }
+                    if let Some(moved_value) = moved_value {
+                        net.add_arc(moved_value.owned_place, t).expect("arc error");
+                        net.add_arc(t, moved_value.empty_place).expect("arc error");
+                    }
+                }
+                self.propagate_links(new_value, replaced);
+            }
 
             Assert {
                 ref cond,
@@ -475,9 +2782,11 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
                 .assert(net, cond, *expected, *target, *cleanup)
                 .expect("assert failed"),
 
-            Yield { .. } => panic!("Yield"),
-            GeneratorDrop => panic!("GeneratorDrop"),
-            DropAndReplace { .. } => panic!("DropAndReplace"),
+            // only the optimized mir is translated, in which the generator transform has
+            // turned every generator into a state machine that returns instead
+            Yield { .. } => bug!("yield points before the generator transform are not supported"),
+            // the end of the generator drop shim
+            GeneratorDrop => function!(self).retorn(net).expect("return failed"),
             Resume => {
                 function!(self)
                     .resume(net, self.unwind_abort_place)
@@ -496,6 +2805,8 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
             ),
             Unreachable => debug!("unreachable"),
         }
+        self.call_spans.pop();
+        self.loop_calls.pop();
         self.super_terminator_kind(kind, location);
     }
 }
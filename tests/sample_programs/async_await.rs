@@ -0,0 +1,50 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+async fn increment(value: &Mutex<u32>) {
+    *value.lock().unwrap() += 1;
+}
+
+async fn run(value: &Mutex<u32>) {
+    increment(value).await;
+    increment(value).await;
+}
+
+fn noop_raw_waker() -> RawWaker {
+    fn clone(_: *const ()) -> RawWaker {
+        noop_raw_waker()
+    }
+    fn noop(_: *const ()) {}
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    RawWaker::new(std::ptr::null(), &VTABLE)
+}
+
+// a minimal executor that polls the future until it is ready,
+// granite replaces it with its executor model
+fn block_on<F: Future>(mut future: F) -> F::Output {
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+// not an executor, its body is translated as usual
+mod blocking {
+    use std::sync::Mutex;
+
+    pub fn block_on(value: &Mutex<u32>) {
+        *value.lock().unwrap() += 1;
+    }
+}
+
+pub fn main() {
+    let value = Mutex::new(0);
+    block_on(run(&value));
+    blocking::block_on(&value);
+}
@@ -0,0 +1,23 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+pub fn main() {
+    let lock = Arc::new(AtomicBool::new(false));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let thread_lock = lock.clone();
+    let thread_counter = counter.clone();
+    let handle = thread::spawn(move || {
+        // spin until the lock is acquired
+        while thread_lock.compare_and_swap(false, true, Ordering::Acquire) {}
+        thread_counter.fetch_add(1, Ordering::Relaxed);
+        thread_lock.store(false, Ordering::Release);
+    });
+    while lock
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {}
+    counter.fetch_add(1, Ordering::Relaxed);
+    lock.store(false, Ordering::Release);
+    handle.join().unwrap();
+}
@@ -0,0 +1,13 @@
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+pub fn main() {
+    let threads = std::env::args().count();
+    let barrier = Arc::new(Barrier::new(threads));
+    let other = barrier.clone();
+    let handle = thread::spawn(move || {
+        other.wait();
+    });
+    barrier.wait();
+    handle.join().expect("thread::spawn failed");
+}
@@ -0,0 +1,16 @@
+use std::sync::{Arc, Barrier, Once};
+use std::thread;
+
+pub fn main() {
+    let init = Arc::new(Once::new());
+    let barrier = Arc::new(Barrier::new(2));
+    let worker_init = init.clone();
+    let worker_barrier = barrier.clone();
+    let worker = thread::spawn(move || {
+        worker_init.call_once(|| {});
+        worker_barrier.wait();
+    });
+    init.call_once(|| {});
+    barrier.wait();
+    worker.join().expect("thread::spawn failed");
+}
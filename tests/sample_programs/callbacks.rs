@@ -0,0 +1,21 @@
+use std::sync::Mutex;
+
+fn increment(value: &Mutex<u32>) {
+    *value.lock().unwrap() += 1;
+}
+
+fn apply<F: Fn(&Mutex<u32>)>(callback: F, value: &Mutex<u32>) {
+    callback(value)
+}
+
+pub fn main() {
+    let value = Mutex::new(0);
+    // function pointer
+    let pointer: fn(&Mutex<u32>) = increment;
+    pointer(&value);
+    // closure with a captured upvar
+    let add = |amount: u32| *value.lock().unwrap() += amount;
+    add(2);
+    // closure passed to a generic function
+    apply(|value| increment(value), &value);
+}
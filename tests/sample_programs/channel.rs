@@ -0,0 +1,11 @@
+use std::sync::mpsc;
+use std::thread;
+
+pub fn main() {
+    let (tx, rx) = mpsc::sync_channel(1);
+    let producer = thread::spawn(move || {
+        tx.send(42).unwrap();
+    });
+    let _value = rx.recv();
+    producer.join().expect("thread::spawn failed");
+}
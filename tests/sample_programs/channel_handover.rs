@@ -0,0 +1,16 @@
+use std::sync::mpsc;
+use std::thread;
+
+pub fn main() {
+    // the queued message is received although the sender is gone
+    let (tx, rx) = mpsc::sync_channel(1);
+    tx.send(1).unwrap();
+    drop(tx);
+    let _queued = rx.recv();
+
+    // `try_send` succeeds on a rendezvous channel if the receiver already waits
+    let (tx, rx) = mpsc::sync_channel(0);
+    let receiver = thread::spawn(move || rx.recv());
+    while tx.try_send(2).is_err() {}
+    receiver.join().expect("thread::spawn failed").unwrap();
+}
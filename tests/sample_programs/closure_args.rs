@@ -0,0 +1,12 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let first = Mutex::new(1);
+    let second = Mutex::new(2);
+    // every parameter refers to its own mutex, not to the whole argument tuple
+    let lock_both = |outer: &Mutex<u32>, inner: &Mutex<u32>| {
+        let _outer = outer.lock().unwrap();
+        let _inner = inner.lock().unwrap();
+    };
+    lock_both(&first, &second);
+}
@@ -0,0 +1,27 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub fn main() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = pair.clone();
+    let notifier = thread::spawn(move || {
+        let (lock, cvar) = &*pair2;
+        let mut started = lock.lock().unwrap();
+        *started = true;
+        cvar.notify_one();
+    });
+    let (lock, cvar) = &*pair;
+    let mut started = lock.lock().unwrap();
+    while !*started {
+        let result = cvar
+            .wait_timeout(started, Duration::from_millis(10))
+            .unwrap();
+        started = result.0;
+        if result.1.timed_out() {
+            break;
+        }
+    }
+    drop(started);
+    notifier.join().expect("thread::spawn failed");
+}
@@ -0,0 +1,16 @@
+use std::sync::{Mutex, MutexGuard};
+
+struct Holder<'a> {
+    guard: MutexGuard<'a, u32>,
+}
+
+pub fn main() {
+    let first = Mutex::new(1);
+    let second = Mutex::new(2);
+    let mut holder = Holder {
+        guard: first.lock().unwrap(),
+    };
+    // the old guard is dropped and unlocks the first mutex before the assignment
+    holder.guard = second.lock().unwrap();
+    let _relocked = first.lock().unwrap();
+}
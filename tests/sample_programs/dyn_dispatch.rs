@@ -0,0 +1,26 @@
+use std::sync::Mutex;
+
+trait Handler {
+    fn handle(&self, lock: &Mutex<u32>);
+}
+
+struct Locking;
+struct Ignoring;
+
+impl Handler for Locking {
+    fn handle(&self, lock: &Mutex<u32>) {
+        *lock.lock().unwrap() += 1;
+    }
+}
+
+impl Handler for Ignoring {
+    fn handle(&self, _lock: &Mutex<u32>) {}
+}
+
+pub fn main() {
+    let lock = Mutex::new(0);
+    let handlers: Vec<Box<dyn Handler>> = vec![Box::new(Locking), Box::new(Ignoring)];
+    let _guard = lock.lock().unwrap();
+    // only the Locking handler deadlocks
+    handlers[0].handle(&lock);
+}
@@ -0,0 +1,29 @@
+use std::sync::Mutex;
+
+/// Locks the mutexes in the given order, called with both orders this can deadlock.
+pub fn transfer(from: &Mutex<u32>, to: &Mutex<u32>) {
+    let mut from = from.lock().unwrap();
+    let mut to = to.lock().unwrap();
+    *from -= 1;
+    *to += 1;
+}
+
+pub fn reset(account: &Mutex<u32>) {
+    *account.lock().unwrap() = 0;
+}
+
+mod internal {
+    // public, but not reachable from outside the crate
+    pub fn helper(account: &super::Mutex<u32>) {
+        super::reset(account);
+    }
+}
+
+fn main() {}
+
+#[test]
+fn transfer_test() {
+    let a = Mutex::new(1);
+    let b = Mutex::new(0);
+    transfer(&a, &b);
+}
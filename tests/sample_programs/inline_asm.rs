@@ -0,0 +1,14 @@
+#![feature(asm)]
+
+use std::sync::Mutex;
+
+pub fn main() {
+    let value = Mutex::new(1u64);
+    let mut guard = value.lock().unwrap();
+    let input = *guard;
+    let output: u64;
+    unsafe {
+        asm!("mov $1, $0" : "=r"(output) : "r"(input));
+    }
+    *guard = output;
+}
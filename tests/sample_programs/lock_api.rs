@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// a hand written lock that is mapped onto the mutex net pattern by lock_api.toml
+pub struct SpinLock {
+    locked: AtomicBool,
+}
+
+pub struct SpinGuard<'a> {
+    lock: &'a SpinLock,
+}
+
+impl SpinLock {
+    pub fn new() -> Self {
+        SpinLock {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    pub fn lock(&self) -> SpinGuard<'_> {
+        while self.locked.compare_and_swap(false, true, Ordering::Acquire) {}
+        SpinGuard { lock: self }
+    }
+
+    pub fn unlock(guard: SpinGuard<'_>) {
+        drop(guard)
+    }
+
+    // not an acquire, although its path starts with the one of `lock`
+    pub fn lock_count(&self) -> usize {
+        self.locked.load(Ordering::Relaxed) as usize
+    }
+}
+
+// a lock without guards that is released by a method call
+pub struct RawSpinLock {
+    locked: AtomicBool,
+}
+
+impl RawSpinLock {
+    pub fn new() -> Self {
+        RawSpinLock {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    pub fn lock(&self) {
+        while self.locked.compare_and_swap(false, true, Ordering::Acquire) {}
+    }
+
+    pub fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<'a> Drop for SpinGuard<'a> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+pub fn main() {
+    let lock = SpinLock::new();
+    let guard = lock.lock();
+    SpinLock::unlock(guard);
+    let _guard = lock.lock();
+    let _count = lock.lock_count();
+    let raw = RawSpinLock::new();
+    raw.lock();
+    raw.unlock();
+}
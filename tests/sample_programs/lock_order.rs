@@ -0,0 +1,16 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn main() {
+    let a = Arc::new(Mutex::new(0));
+    let b = Arc::new(Mutex::new(0));
+    let (a2, b2) = (a.clone(), b.clone());
+    let handle = thread::spawn(move || {
+        let _b = b2.lock();
+        let _a = a2.lock();
+    });
+    let _a = a.lock();
+    let _b = b.lock();
+    drop((_a, _b));
+    handle.join().unwrap();
+}
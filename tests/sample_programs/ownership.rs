@@ -0,0 +1,14 @@
+use std::sync::{Mutex, MutexGuard};
+
+fn release(guard: MutexGuard<i32>) {
+    // the guard was moved here, so only this drop unlocks the mutex
+    drop(guard);
+}
+
+pub fn main() {
+    let data = Mutex::new(0);
+    let guard = data.lock().unwrap();
+    let moved = guard;
+    release(moved);
+    let _again = data.lock().unwrap();
+}
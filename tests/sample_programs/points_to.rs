@@ -0,0 +1,26 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+struct Account {
+    balance: Arc<Mutex<u32>>,
+}
+
+fn open_account() -> Account {
+    // the mutex is returned from the function that allocates it
+    Account {
+        balance: Arc::new(Mutex::new(0)),
+    }
+}
+
+pub fn main() {
+    let accounts = vec![open_account(), open_account()];
+    let first = Arc::clone(&accounts[0].balance);
+    let handle = thread::spawn(move || {
+        *first.lock().unwrap() += 1;
+    });
+    // may be either account, the lock branches over both mutexes
+    if let Some(account) = accounts.get(1) {
+        *account.balance.lock().unwrap() += 1;
+    }
+    handle.join().unwrap();
+}
@@ -0,0 +1,13 @@
+use std::sync::Mutex;
+
+fn count_down(counter: &Mutex<usize>, depth: usize) {
+    if depth > 0 {
+        *counter.lock().unwrap() += 1;
+        count_down(counter, depth - 1)
+    }
+}
+
+pub fn main() {
+    let counter = Mutex::new(0);
+    count_down(&counter, 10);
+}
@@ -0,0 +1,17 @@
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+pub fn main() {
+    let config = Arc::new(RwLock::new(0));
+    let reader_config = config.clone();
+    let reader = thread::spawn(move || {
+        let _value = reader_config.read();
+    });
+    {
+        let _value = config.read();
+    }
+    {
+        let _value = config.write();
+    }
+    reader.join().expect("thread::spawn failed");
+}
@@ -0,0 +1,16 @@
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+pub fn main() {
+    let config = Arc::new(RwLock::new(0));
+    let writer_config = config.clone();
+    let writer = thread::spawn(move || {
+        let _value = writer_config.write();
+    });
+    {
+        let _outer = config.read();
+        // std allows a thread to read a lock it already reads
+        let _inner = config.read();
+    }
+    writer.join().expect("thread::spawn failed");
+}
@@ -0,0 +1,22 @@
+use std::sync::Mutex;
+
+struct Pair {
+    left: Mutex<u32>,
+    right: Mutex<u32>,
+}
+
+impl Pair {
+    // both fields are reached through the reference to the pair
+    fn lock_both(&self) {
+        let _left = self.left.lock().unwrap();
+        let _right = self.right.lock().unwrap();
+    }
+}
+
+pub fn main() {
+    let pair = Pair {
+        left: Mutex::new(0),
+        right: Mutex::new(1),
+    };
+    pair.lock_both();
+}
@@ -0,0 +1,9 @@
+use std::sync::{Arc, Mutex};
+
+pub fn main() {
+    let data = Arc::new(Mutex::new(0));
+    {
+        let _d1 = data.lock();
+    } // d1 is dropped and releases the mutex
+    let _d2 = data.lock();
+}
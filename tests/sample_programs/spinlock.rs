@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+fn lock(locked: &AtomicBool) {
+    loop {
+        // only a successful exchange leaves the loop
+        if let Ok(_) = locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed) {
+            break;
+        }
+    }
+}
+
+fn unlock(locked: &AtomicBool) {
+    locked.store(false, Ordering::Release);
+}
+
+pub fn main() {
+    let locked = Arc::new(AtomicBool::new(false));
+    let other = locked.clone();
+    let handle = thread::spawn(move || {
+        lock(&other);
+        unlock(&other);
+    });
+    lock(&locked);
+    unlock(&locked);
+    handle.join().expect("thread::spawn failed");
+}
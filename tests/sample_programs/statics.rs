@@ -0,0 +1,21 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+use std::thread;
+
+static INIT: Once = Once::new();
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn initialize() {
+    INIT.call_once(|| {
+        COUNTER.store(1, Ordering::SeqCst);
+    });
+}
+
+pub fn main() {
+    let handle = thread::spawn(|| {
+        initialize();
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+    });
+    initialize();
+    handle.join().unwrap();
+}
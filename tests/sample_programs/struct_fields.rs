@@ -0,0 +1,18 @@
+use std::sync::Mutex;
+
+struct Accounts {
+    checking: Mutex<u32>,
+    savings: Mutex<u32>,
+}
+
+pub fn main() {
+    let accounts = Accounts {
+        checking: Mutex::new(10),
+        savings: Mutex::new(20),
+    };
+    // distinct fields are distinct locks, this never deadlocks
+    let checking = accounts.checking.lock().unwrap();
+    let savings = accounts.savings.lock().unwrap();
+    drop(savings);
+    drop(checking);
+}
@@ -0,0 +1,18 @@
+use std::sync::Mutex;
+
+fn compute(value: i32) -> i32 {
+    value * 2
+}
+
+fn with_lock(mutex: &Mutex<i32>) -> i32 {
+    let _guard = mutex.lock().unwrap();
+    compute(2)
+}
+
+pub fn main() {
+    let first = Mutex::new(1);
+    let second = Mutex::new(2);
+    let _first = with_lock(&first);
+    let _second = with_lock(&second);
+    let _again = with_lock(&first);
+}
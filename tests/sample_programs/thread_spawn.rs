@@ -0,0 +1,9 @@
+use std::thread;
+
+pub fn main() {
+    let handle = thread::spawn(|| {
+        let x = 5;
+        x * 2
+    });
+    handle.join().expect("thread::spawn failed");
+}
@@ -0,0 +1,29 @@
+use std::sync::{Mutex, MutexGuard};
+
+struct Shared {
+    data: Mutex<u32>,
+}
+
+trait Access {
+    fn access(&self) -> MutexGuard<'_, u32>;
+}
+
+impl Access for Shared {
+    fn access(&self) -> MutexGuard<'_, u32> {
+        self.data.lock().unwrap()
+    }
+}
+
+// the call to access is resolved to the impl of Shared
+fn access_generic<A: Access>(shared: &A) -> u32 {
+    let guard = shared.access();
+    *guard
+}
+
+pub fn main() {
+    let shared = Shared {
+        data: Mutex::new(0),
+    };
+    access_generic(&shared);
+    let _guard = shared.access();
+}
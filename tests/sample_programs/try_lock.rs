@@ -0,0 +1,12 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let data = Mutex::new(0);
+    let _d1 = data.lock();
+    loop {
+        // never succeeds while d1 is alive
+        if let Ok(_d2) = data.try_lock() {
+            break;
+        }
+    }
+}
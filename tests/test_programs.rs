@@ -4,15 +4,17 @@ use assert_cmd::prelude::*; // Add methods on commands
 use std::process::Command; // Run programs // Used for writing assertions
 
 fn test_program(path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::main_binary()?;
+    test_program_with_args(path, &[])
+}
+
+fn test_program_with_args(path: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = granite();
     cmd.arg(path);
-    cmd.env("RUST_BACKTRACE", "1");
-    cmd.env("RUST_LOG", "trace");
-    // has to point to the toolchain declared in ``rust-toolchain`` file
-    cmd.env(
-        "LD_LIBRARY_PATH",
-        "/home/tom/.rustup/toolchains/nightly-2020-01-07-x86_64-unknown-linux-gnu/lib",
-    );
+    if !args.is_empty() {
+        // granite arguments are separated from rustc arguments
+        cmd.arg("--");
+        cmd.args(args);
+    }
 
     let result = cmd.assert().success();
     // run 'cargo test -- --nocapture' to see the actual output
@@ -23,6 +25,110 @@ fn test_program(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// The standard output of a translation, e.g. the net of `--output pnml=-`.
+fn program_output(path: &str, args: &[&str]) -> String {
+    program_output_of(granite().arg(path).arg("--").args(args))
+}
+
+fn program_output_of(cmd: &mut Command) -> String {
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(stdout).expect("output is not utf8")
+}
+
+/// The id of the place with the given name in a pnml net.
+fn place_id<'a>(pnml: &'a str, place: &str) -> Option<&'a str> {
+    let name = pnml.find(&format!("<text>{}</text>", place))?;
+    let id = pnml[..name].rfind("<place id=\"")? + "<place id=\"".len();
+    Some(&pnml[id..id + pnml[id..].find('"')?])
+}
+
+/// The sources and targets of all arcs in a pnml net.
+fn arcs(pnml: &str) -> Vec<(&str, &str)> {
+    pnml.split("<arc ")
+        .skip(1)
+        .filter_map(|arc| {
+            let tag = &arc[..arc.find('>')?];
+            Some((attribute(tag, "source")?, attribute(tag, "target")?))
+        })
+        .collect()
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    Some(&tag[start..start + tag[start..].find('"')?])
+}
+
+/// The transitions that consume from the place with the given id.
+fn consumers<'a>(pnml: &'a str, place: &str) -> Vec<&'a str> {
+    arcs(pnml)
+        .into_iter()
+        .filter(|(source, _)| *source == place)
+        .map(|(_, target)| target)
+        .collect()
+}
+
+/// The places the transition with the given id consumes from.
+fn preset<'a>(pnml: &'a str, transition: &str) -> Vec<&'a str> {
+    arcs(pnml)
+        .into_iter()
+        .filter(|(_, target)| *target == transition)
+        .map(|(source, _)| source)
+        .collect()
+}
+
+/// The places the transition with the given id produces to.
+fn postset<'a>(pnml: &'a str, transition: &str) -> Vec<&'a str> {
+    arcs(pnml)
+        .into_iter()
+        .filter(|(source, _)| *source == transition)
+        .map(|(_, target)| target)
+        .collect()
+}
+
+/// The places the transition with the given id reads, i.e. consumes and produces again.
+fn read_places<'a>(pnml: &'a str, transition: &str) -> Vec<&'a str> {
+    let postset = postset(pnml, transition);
+    preset(pnml, transition)
+        .into_iter()
+        .filter(|place| postset.contains(place))
+        .collect()
+}
+
+/// The ids of the transitions whose name contains the given text.
+fn transitions_named<'a>(pnml: &'a str, name: &str) -> Vec<&'a str> {
+    pnml.split("<transition id=\"")
+        .skip(1)
+        .filter_map(|transition| {
+            let end = transition.find("</transition>")?;
+            if transition[..end].contains(name) {
+                Some(&transition[..transition.find('"')?])
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether a transition consumes from the place with the given name in a pnml net.
+fn is_consumed(pnml: &str, place: &str) -> bool {
+    match place_id(pnml, place) {
+        Some(id) => !consumers(pnml, id).is_empty(),
+        None => false,
+    }
+}
+
+fn granite() -> Command {
+    let mut cmd = Command::cargo_bin("granite").expect("granite binary not found");
+    cmd.env("RUST_BACKTRACE", "1");
+    cmd.env("RUST_LOG", "trace");
+    // has to point to the toolchain declared in ``rust-toolchain`` file
+    cmd.env(
+        "LD_LIBRARY_PATH",
+        "/home/tom/.rustup/toolchains/nightly-2020-01-07-x86_64-unknown-linux-gnu/lib",
+    );
+    cmd
+}
+
 #[test]
 fn minimal_program_test() {
     test_program("tests/sample_programs/minimal_program.rs").unwrap();
@@ -42,3 +148,546 @@ fn minimal_deadlock_test() {
 fn function_call_test() {
     test_program("tests/sample_programs/function_call.rs").unwrap();
 }
+
+#[test]
+fn thread_spawn_test() {
+    test_program("tests/sample_programs/thread_spawn.rs").unwrap();
+}
+
+#[test]
+fn sequential_lock_test() {
+    test_program("tests/sample_programs/sequential_lock.rs").unwrap();
+}
+
+#[test]
+fn try_lock_test() {
+    test_program("tests/sample_programs/try_lock.rs").unwrap();
+}
+
+#[test]
+fn rwlock_test() {
+    test_program("tests/sample_programs/rwlock.rs").unwrap();
+}
+
+#[test]
+fn rwlock_reentrant_test() {
+    let pnml = program_output(
+        "tests/sample_programs/rwlock_reentrant.rs",
+        &["--output", "pnml=-"],
+    );
+    let outer_slot = place_id(&pnml, "RwLock_0 read slot 0").expect("no outer read slot");
+    let inner_slot = place_id(&pnml, "RwLock_0 read slot 1").expect("no inner read slot");
+    let writer_free = place_id(&pnml, "RwLock_0 writer free").expect("no writer token");
+    // the writer waits until both reads are released
+    assert!(consumers(&pnml, writer_free).iter().any(|write| {
+        let preset = preset(&pnml, write);
+        preset.contains(&outer_slot) && preset.contains(&inner_slot)
+    }));
+    // the nested read needs neither the writer token nor the slot of the outer read,
+    // so it can fire while the writer waits for the outer read
+    let nested_reads: Vec<&str> = consumers(&pnml, inner_slot)
+        .into_iter()
+        .filter(|read| !preset(&pnml, read).contains(&writer_free))
+        .collect();
+    assert!(!nested_reads.is_empty());
+    for read in nested_reads {
+        assert!(!preset(&pnml, read).contains(&outer_slot));
+    }
+}
+
+#[test]
+fn condvar_test() {
+    test_program("tests/sample_programs/condvar.rs").unwrap();
+}
+
+#[test]
+fn condvar_timeout_test() {
+    let pnml = program_output(
+        "tests/sample_programs/condvar_timeout.rs",
+        &["--output", "pnml=-"],
+    );
+    // a timeout wakes up without notification and the switch on `timed_out` reads it
+    let timed_out =
+        place_id(&pnml, "std::sync::Condvar::wait_timeout timed out").expect("no timeout place");
+    assert!(!consumers(&pnml, timed_out).is_empty());
+    assert!(is_consumed(
+        &pnml,
+        "std::sync::Condvar::wait_timeout timeout succeeded"
+    ));
+    assert!(is_consumed(
+        &pnml,
+        "std::sync::Condvar::wait_timeout timeout failed"
+    ));
+    // a plain wait never times out
+    let pnml = program_output("tests/sample_programs/condvar.rs", &["--output", "pnml=-"]);
+    assert!(!pnml.contains("timed out"));
+    assert!(!pnml.contains("without notification"));
+}
+
+#[test]
+fn channel_test() {
+    test_program("tests/sample_programs/channel.rs").unwrap();
+}
+
+#[test]
+fn channel_handover_test() {
+    let pnml = program_output(
+        "tests/sample_programs/channel_handover.rs",
+        &["--output", "pnml=-"],
+    );
+    // the disconnected `recv` reads the free slots of the bounded channel
+    assert!(pnml.contains("Channel_0 slot 0 free"));
+    assert!(pnml.contains("Channel_1 handed over"));
+}
+
+#[test]
+fn barrier_once_test() {
+    test_program("tests/sample_programs/barrier_once.rs").unwrap();
+}
+
+#[test]
+fn barrier_dynamic_test() {
+    let pnml = program_output(
+        "tests/sample_programs/barrier_dynamic.rs",
+        &["--output", "pnml=-"],
+    );
+    // the number of threads is not known, so every wait may block
+    assert!(pnml.contains("std::sync::Barrier::wait block"));
+}
+
+#[test]
+fn atomic_test() {
+    test_program("tests/sample_programs/atomic.rs").unwrap();
+}
+
+#[test]
+fn spinlock_test() {
+    let pnml = program_output("tests/sample_programs/spinlock.rs", &["--output", "pnml=-"]);
+    // the switch on the result follows the outcome of the exchange
+    assert!(pnml.contains("std::sync::atomic::AtomicBool::compare_exchange succeeded"));
+    assert!(pnml.contains("std::sync::atomic::AtomicBool::compare_exchange failed"));
+}
+
+#[test]
+fn lock_api_test() {
+    let pnml = program_output(
+        "tests/sample_programs/lock_api.rs",
+        &[
+            "--lock-api",
+            "tests/sample_programs/lock_api.toml",
+            "--output",
+            "pnml=-",
+        ],
+    );
+    // only the two calls of `lock` acquire the spin lock, `lock_count` does not
+    let unlocked = place_id(&pnml, "Mutex_0 unlocked").expect("no spin lock");
+    assert_eq!(consumers(&pnml, unlocked).len(), 2);
+    // the raw lock is released by the method call
+    assert!(is_consumed(&pnml, "Mutex_1 locked"));
+}
+
+#[test]
+fn recursion_test() {
+    test_program("tests/sample_programs/recursion.rs").unwrap();
+}
+
+#[test]
+fn function_call_summaries_test() {
+    test_program_with_args("tests/sample_programs/function_call.rs", &["--summaries"]).unwrap();
+}
+
+#[test]
+fn recursion_summaries_test() {
+    test_program_with_args("tests/sample_programs/recursion.rs", &["--summaries"]).unwrap();
+}
+
+#[test]
+fn summary_call_sites_test() {
+    let pnml = program_output(
+        "tests/sample_programs/summary_call_sites.rs",
+        &["--summaries", "--output", "pnml=-"],
+    );
+    // calls with different mutexes keep their own summary,
+    // calls with the same mutex share it
+    assert_eq!(pnml.matches("<text>with_lock entry</text>").count(), 2);
+    assert!(pnml.contains("with_lock call 1"));
+    assert!(!pnml.contains("with_lock call 2"));
+    // both call sites of the function without links share one summary
+    assert!(pnml.contains("compute call 0"));
+    assert!(pnml.contains("compute call 1"));
+}
+
+#[test]
+fn drop_and_replace_test() {
+    // drop elaboration splits the replacement into a drop and an assignment,
+    // the guard of the first mutex has to be released in between
+    let mir = program_output(
+        "tests/sample_programs/drop_and_replace.rs",
+        &["--mir_dump", "-"],
+    );
+    assert!(mir.contains("drop((_"));
+    assert!(mir.contains(".0: std::sync::MutexGuard"));
+    let pnml = program_output(
+        "tests/sample_programs/drop_and_replace.rs",
+        &["--output", "pnml=-"],
+    );
+    // the drop of the replaced guard reads the replaced and the new local
+    let locked = place_id(&pnml, "Mutex_0 locked").expect("no lock of the first mutex");
+    assert!(consumers(&pnml, locked)
+        .iter()
+        .any(|release| read_places(&pnml, release).len() >= 2));
+}
+
+#[test]
+fn inline_asm_test() {
+    let pnml = program_output(
+        "tests/sample_programs/inline_asm.rs",
+        &["--output", "pnml=-"],
+    );
+    // the assembly may access both of its operands
+    assert!(transitions_named(&pnml, "asm!(")
+        .iter()
+        .any(|asm| read_places(&pnml, asm).len() >= 2));
+    // the guard is still released afterwards
+    assert!(is_consumed(&pnml, "Mutex_0 locked"));
+}
+
+#[test]
+fn trait_call_test() {
+    test_program("tests/sample_programs/trait_call.rs").unwrap();
+}
+
+#[test]
+fn dyn_dispatch_test() {
+    test_program("tests/sample_programs/dyn_dispatch.rs").unwrap();
+}
+
+#[test]
+fn callbacks_test() {
+    test_program("tests/sample_programs/callbacks.rs").unwrap();
+}
+
+#[test]
+fn closure_args_test() {
+    let pnml = program_output(
+        "tests/sample_programs/closure_args.rs",
+        &["--output", "pnml=-"],
+    );
+    // each lock in the closure takes exactly the mutex main passed for its parameter,
+    // a parameter linked to the whole argument tuple would lock either of them
+    for mutex in &["Mutex_0 unlocked", "Mutex_1 unlocked"] {
+        let unlocked = place_id(&pnml, mutex).expect("mutex of main not found");
+        assert_eq!(consumers(&pnml, unlocked).len(), 1);
+    }
+    assert!(!pnml.contains("Mutex_2"));
+}
+
+#[test]
+fn async_await_test() {
+    let pnml = program_output(
+        "tests/sample_programs/async_await.rs",
+        &["--output", "pnml=-"],
+    );
+    // the future is polled by the executor model until it is ready
+    assert!(pnml.contains("block_on pinned future"));
+    assert!(pnml.contains("block_on pending"));
+    assert!(pnml.contains("block_on ready"));
+    // after each poll the executor either returns or polls again
+    let polled = place_id(&pnml, "block_on polled").expect("no poll result");
+    assert_eq!(consumers(&pnml, polled).len(), 2);
+    // the polled future locks the mutex of main
+    assert!(is_consumed(&pnml, "Mutex_0 unlocked"));
+    assert!(!pnml.contains("Mutex_1"));
+    // a `block_on` without a future is no executor
+    assert!(!pnml.contains("blocking::block_on polled"));
+}
+
+#[test]
+fn statics_test() {
+    test_program("tests/sample_programs/statics.rs").unwrap();
+}
+
+#[test]
+fn struct_fields_test() {
+    test_program("tests/sample_programs/struct_fields.rs").unwrap();
+    test_program_with_args(
+        "tests/sample_programs/struct_fields.rs",
+        &["--field-depth", "0"],
+    )
+    .unwrap();
+}
+
+#[test]
+fn self_fields_test() {
+    let pnml = program_output(
+        "tests/sample_programs/self_fields.rs",
+        &["--output", "pnml=-"],
+    );
+    // each field behind `&self` is locked on its own mutex
+    assert!(is_consumed(&pnml, "Mutex_0 unlocked"));
+    assert!(is_consumed(&pnml, "Mutex_1 unlocked"));
+}
+
+#[test]
+fn ownership_test() {
+    let pnml = program_output(
+        "tests/sample_programs/ownership.rs",
+        &["--ownership", "--output", "pnml=-"],
+    );
+    // a drop is skipped if the value was moved out of the local before
+    assert!(pnml.contains("drop moved"));
+}
+
+#[test]
+fn thread_spawn_ownership_test() {
+    test_program_with_args("tests/sample_programs/thread_spawn.rs", &["--ownership"]).unwrap();
+}
+
+#[test]
+fn points_to_test() {
+    let pnml = program_output(
+        "tests/sample_programs/points_to.rs",
+        &["--output", "pnml=-"],
+    );
+    // both accounts have their own mutex and the locks may take either
+    assert!(is_consumed(&pnml, "Mutex_0 unlocked"));
+    assert!(is_consumed(&pnml, "Mutex_1 unlocked"));
+    assert!(!pnml.contains("Mutex_2"));
+}
+
+#[test]
+fn loop_allocation_test() {
+    let pnml = program_output(
+        "tests/sample_programs/dining_philosophers.rs",
+        &["--output", "pnml=-"],
+    );
+    // the forks allocated in the loop do not collapse into one mutex
+    assert!(is_consumed(&pnml, "Mutex_0 unlocked"));
+    assert!(is_consumed(&pnml, "Mutex_1 unlocked"));
+    assert!(!pnml.contains("Mutex_2"));
+    assert!(pnml.contains("::new reuse"));
+    let pnml = program_output(
+        "tests/sample_programs/dining_philosophers.rs",
+        &["--loop-instances", "5", "--output", "pnml=-"],
+    );
+    assert!(is_consumed(&pnml, "Mutex_4 unlocked"));
+}
+
+#[test]
+fn entry_points_test() {
+    test_program_with_args(
+        "tests/sample_programs/entry_points.rs",
+        &["--entry", "transfer"],
+    )
+    .unwrap();
+    test_program_with_args(
+        "tests/sample_programs/entry_points.rs",
+        &["--entries", "pub"],
+    )
+    .unwrap();
+    test_program_with_args(
+        "tests/sample_programs/entry_points.rs",
+        &["--entries", "pub", "--combined"],
+    )
+    .unwrap();
+    // only the exported functions get a net
+    test_program_with_args(
+        "tests/sample_programs/entry_points.rs",
+        &["--entries", "pub", "--out-dir", "target/granite-entries"],
+    )
+    .unwrap();
+    let out_dir = std::path::Path::new("target/granite-entries");
+    assert!(out_dir.join("entry_points_transfer.pnml").exists());
+    assert!(out_dir.join("entry_points_reset.pnml").exists());
+    assert!(!out_dir.join("entry_points_internal__helper.pnml").exists());
+    assert!(!out_dir.join("entry_points_main.pnml").exists());
+    granite()
+        .arg("tests/sample_programs/entry_points.rs")
+        .args(&["--", "--entries", "public"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_entries_test() {
+    // the tests only exist in the test harness
+    let mut cmd = granite();
+    cmd.arg("--test")
+        .arg("tests/sample_programs/entry_points.rs")
+        .args(&["--", "--entries", "tests", "--output", "pnml=-"]);
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let pnml = String::from_utf8(stdout).expect("output is not utf8");
+    // `transfer_test` locks both of its mutexes
+    assert!(is_consumed(&pnml, "Mutex_0 unlocked"));
+    assert!(is_consumed(&pnml, "Mutex_1 unlocked"));
+}
+
+#[test]
+fn output_paths_test() {
+    test_program_with_args(
+        "tests/sample_programs/entry_points.rs",
+        &[
+            "--entries",
+            "pub",
+            "--format",
+            "lola",
+            "dot",
+            "--out-dir",
+            "target/granite-test",
+            "--output-prefix",
+            "entries",
+            "--mir_dump",
+        ],
+    )
+    .unwrap();
+    test_program_with_args(
+        "tests/sample_programs/entry_points.rs",
+        &["--entry", "transfer", "--output", "lola=-"],
+    )
+    .unwrap();
+    // only one output can go to stdout
+    granite()
+        .arg("tests/sample_programs/entry_points.rs")
+        .args(&[
+            "--",
+            "--entry",
+            "transfer",
+            "--output",
+            "lola=-",
+            "--mir_dump",
+            "-",
+        ])
+        .assert()
+        .failure();
+    let rejected: &[&[&str]] = &[
+        &[
+            "--entry", "transfer", "--output", "pnml=-", "--output", "lola=-",
+        ],
+        // separate nets of several entries
+        &["--entries", "pub", "--output", "pnml=-"],
+        // numeric arguments
+        &["--entry", "transfer", "--recursion-depth", "deep"],
+        &["--entry", "transfer", "--field-depth", "-1"],
+        &["--entry", "transfer", "--loop-instances", "two"],
+    ];
+    for args in rejected {
+        granite()
+            .arg("tests/sample_programs/entry_points.rs")
+            .arg("--")
+            .args(*args)
+            .assert()
+            .failure();
+    }
+}
+
+#[test]
+fn source_spans_test() {
+    test_program_with_args(
+        "tests/sample_programs/dining_philosophers.rs",
+        &["--format", "pnml", "dot", "spans"],
+    )
+    .unwrap();
+    let pnml = program_output(
+        "tests/sample_programs/minimal_deadlock.rs",
+        &["--output", "pnml=-"],
+    );
+    // the lock transitions take the location of their call
+    let locks: Vec<&str> = pnml
+        .split("<transition ")
+        .skip(1)
+        .map(|transition| transition.split("</transition>").next().unwrap_or(""))
+        .filter(|transition| transition.contains("::lock</text>"))
+        .collect();
+    assert_eq!(locks.len(), 2);
+    assert!(locks.iter().any(
+        |lock| lock.contains("kind=\"terminator\"") && lock.contains("minimal_deadlock.rs:7:")
+    ));
+}
+
+/// `granite explain` with the checked-in witness of `lock_order.rs`.
+fn explain(args: &[&str]) -> String {
+    program_output_of(
+        granite()
+            .args(&[
+                "explain",
+                "--spans",
+                "tests/sample_programs/lock_order.spans.json",
+                "--path",
+                "tests/sample_programs/lock_order.path",
+            ])
+            .args(args),
+    )
+}
+
+#[test]
+fn explain_state_test() {
+    let output = explain(&["--state", "tests/sample_programs/lock_order.state"]);
+    assert!(output.contains("witness path with 2 steps in 2 thread(s): Thread_0, main"));
+    let blocked = output
+        .split("blocked operations:")
+        .nth(1)
+        .expect("no blocked operations");
+    // each thread waits for the mutex the other one holds
+    let lines: Vec<&str> = blocked.lines().filter(|line| !line.is_empty()).collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("[Thread_0]     p_5  main::{{closure}}#0 bb 2: "));
+    assert!(lines[0].ends_with("at lock_order.rs:10:18: 10:27"));
+    assert!(lines[1].ends_with("called at lock_order.rs:8:18: 11:7"));
+    assert!(lines[2].starts_with("[main]     p_1  main bb 9: "));
+    assert!(lines[2].ends_with("at lock_order.rs:13:14: 13:22"));
+}
+
+#[test]
+fn explain_replay_test() {
+    // replaying the path on the net reaches the marking of the witness state
+    let replayed = explain(&["--net", "tests/sample_programs/lock_order.lola"]);
+    let state = explain(&["--state", "tests/sample_programs/lock_order.state"]);
+    assert_eq!(replayed, state);
+    // without a marking the last step of every thread is shown
+    let output = explain(&[]);
+    assert!(!output.contains("blocked operations:"));
+    let last_steps = output.split("last steps").nth(1).expect("no last steps");
+    assert!(last_steps.contains("[Thread_0]     t_2"));
+    assert!(last_steps.contains("[main]     t_0"));
+}
+
+#[test]
+#[ignore] // needs lola
+fn explain_lola_test() {
+    test_program_with_args(
+        "tests/sample_programs/minimal_deadlock.rs",
+        &[
+            "--format",
+            "lola",
+            "spans",
+            "--out-dir",
+            "target/granite-explain",
+        ],
+    )
+    .unwrap();
+    // the witness belongs to the net of this build, so lola has to find it
+    Command::new("lola")
+        .args(&[
+            "target/granite-explain/net.lola",
+            "--formula=EF DEADLOCK",
+            "--path=target/granite-explain/path.txt",
+            "--state=target/granite-explain/state.txt",
+        ])
+        .output()
+        .expect("lola not found");
+    let output = program_output_of(granite().args(&[
+        "explain",
+        "--spans",
+        "target/granite-explain/net.spans.json",
+        "--path",
+        "target/granite-explain/path.txt",
+        "--state",
+        "target/granite-explain/state.txt",
+    ]));
+    // the second lock waits for the guard of the first one
+    let blocked = output
+        .split("blocked operations:")
+        .nth(1)
+        .expect("no blocked operations");
+    assert!(blocked.contains("minimal_deadlock.rs:7:"));
+}